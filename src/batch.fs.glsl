@@ -0,0 +1,137 @@
+#version 330 core
+
+// Keep in sync with ShapeKind in graphics/batch.rs
+#define KIND_SOLID 0
+#define KIND_CIRCLE 1
+#define KIND_TEXTURE 2
+#define KIND_GLYPH 3
+#define KIND_SDF_GLYPH 4
+#define KIND_ROUNDED_RECT 5
+
+// Keep in sync with Gradient in graphics/batch.rs
+#define GRADIENT_LINEAR 1
+#define GRADIENT_RADIAL 2
+
+uniform sampler2D tex;
+
+in vec2 v_tex_coords;
+in vec4 v_color;
+flat in int v_kind;
+flat in vec4 v_secondary_color;
+flat in vec4 v_params;
+flat in vec4 v_extra_params;
+flat in vec4 v_gradient_color;
+flat in vec4 v_gradient;
+
+// The fill at p, relative to the shape's center, blended from v_color by the gradient selected in
+// v_extra_params.w
+vec4 fill_color(vec2 p) {
+	int mode = int(v_extra_params.w + 0.5);
+	float t = 0.0;
+	if (mode == GRADIENT_LINEAR) {
+		vec2 direction = v_gradient.zw - v_gradient.xy;
+		t = dot(p - v_gradient.xy, direction) / max(dot(direction, direction), 0.0001);
+	} else if (mode == GRADIENT_RADIAL) {
+		t = length(p - v_gradient.xy) / max(v_gradient.z, 0.0001);
+	}
+	return mix(v_color, v_gradient_color, clamp(t, 0.0, 1.0));
+}
+
+// Fill and stroke coverage put together, the stroke lying over the fill
+vec4 fill_and_stroke(vec4 fill, float fill_coverage, float stroke_coverage) {
+	float fill_alpha = fill.a * fill_coverage;
+	float stroke_alpha = v_secondary_color.a * stroke_coverage;
+	float alpha = fill_alpha + stroke_alpha;
+	if (alpha <= 0.0) {
+		discard;
+	}
+
+	vec3 color = (fill.rgb * fill_alpha + v_secondary_color.rgb * stroke_alpha) / alpha;
+	return vec4(color, alpha);
+}
+
+// How much of a pixel lies inside an edge, given how far outside of it the pixel's center is
+float coverage(float distance) {
+	return clamp(0.5 - distance / max(fwidth(distance), 0.0001), 0.0, 1.0);
+}
+
+// Roughly how far p is outside the ellipse with the given radii, good near its edge
+float ellipse_distance(vec2 p, vec2 radii) {
+	radii = max(radii, vec2(0.0001));
+	float f = dot(p / radii, p / radii) - 1.0;
+	vec2 gradient = 2.0 * p / (radii * radii);
+	return f / max(length(gradient), 0.0001);
+}
+
+// v_params.xy are the radii and v_params.z the stroke width, in drawing units. The stroke is drawn
+// in v_secondary_color inside the edge, the rest is filled with fill_color.
+vec4 ellipse() {
+	vec2 radii = v_params.xy;
+	vec2 p = v_tex_coords * radii;
+
+	float outer = ellipse_distance(p, radii);
+	float inner = ellipse_distance(p, radii - vec2(v_params.z));
+
+	// Half a pixel of anti-aliasing on either side of each edge, whatever the size
+	float outer_coverage = coverage(outer);
+	float inner_coverage = min(coverage(inner), outer_coverage);
+
+	return fill_and_stroke(fill_color(p), inner_coverage, outer_coverage - inner_coverage);
+}
+
+// v_params are the corner radii from the top left going clockwise, v_extra_params.xy the half size
+// and v_extra_params.z the stroke width, in drawing units. v_tex_coords is relative to the center.
+vec4 rounded_rect() {
+	vec2 p = v_tex_coords;
+	vec2 half_size = v_extra_params.xy;
+
+	float radius = p.y > 0.0
+		? (p.x > 0.0 ? v_params.y : v_params.x)
+		: (p.x > 0.0 ? v_params.z : v_params.w);
+
+	vec2 q = abs(p) - half_size + vec2(radius);
+	float outer = min(max(q.x, q.y), 0.0) + length(max(q, vec2(0.0))) - radius;
+	float inner = outer + v_extra_params.z;
+
+	float outer_coverage = coverage(outer);
+	float inner_coverage = min(coverage(inner), outer_coverage);
+
+	return fill_and_stroke(fill_color(p), inner_coverage, outer_coverage - inner_coverage);
+}
+
+// v_params.x is the outline width and v_params.y the extra softness, both in distance field units.
+// The outline is drawn in v_secondary_color behind the fill.
+vec4 sdf_glyph() {
+	float dist = texture(tex, v_tex_coords).r;
+	float smoothing = fwidth(dist) * 0.75 + v_params.y;
+
+	float fill = smoothstep(0.5 - smoothing, 0.5 + smoothing, dist);
+	float outline_edge = 0.5 - v_params.x;
+	float outline = smoothstep(outline_edge - smoothing, outline_edge + smoothing, dist);
+
+	float fill_alpha = v_color.a * fill;
+	float outline_alpha = v_secondary_color.a * outline * (1.0 - fill_alpha);
+	float alpha = fill_alpha + outline_alpha;
+	if (alpha <= 0.0) {
+		discard;
+	}
+
+	vec3 color = (v_color.rgb * fill_alpha + v_secondary_color.rgb * outline_alpha) / alpha;
+	return vec4(color, alpha);
+}
+
+void main() {
+	if (v_kind == KIND_CIRCLE) {
+		gl_FragColor = ellipse();
+	} else if (v_kind == KIND_TEXTURE) {
+		gl_FragColor = v_color * texture(tex, v_tex_coords);
+	} else if (v_kind == KIND_GLYPH) {
+		gl_FragColor = v_color * vec4(1.0, 1.0, 1.0, texture(tex, v_tex_coords).r);
+	} else if (v_kind == KIND_SDF_GLYPH) {
+		gl_FragColor = sdf_glyph();
+	} else if (v_kind == KIND_ROUNDED_RECT) {
+		gl_FragColor = rounded_rect();
+	} else {
+		gl_FragColor = v_color;
+	}
+}
\ No newline at end of file
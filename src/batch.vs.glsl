@@ -0,0 +1,51 @@
+#version 330 core
+
+// Keep in sync with ShapeKind in graphics/batch.rs
+#define KIND_CIRCLE 1
+#define KIND_SDF_GLYPH 4
+#define KIND_ROUNDED_RECT 5
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 tex_coord;
+layout(location = 2) in vec4 color;
+// The kind in the low 4 bits, the index of the shape's first texel in shape_params above them
+layout(location = 3) in uint shape;
+uniform mat4 Projection;
+// Secondary color, params, extra params, gradient color and gradient, as far as the kind needs them
+uniform samplerBuffer shape_params;
+
+out vec2 v_tex_coords;
+out vec4 v_color;
+flat out int v_kind;
+flat out vec4 v_secondary_color;
+flat out vec4 v_params;
+flat out vec4 v_extra_params;
+flat out vec4 v_gradient_color;
+flat out vec4 v_gradient;
+
+void main() {
+	gl_Position = Projection * vec4(position, 0.0, 1.0);
+	v_tex_coords = tex_coord;
+	v_color = color;
+	v_kind = int(shape & 15u);
+
+	// Keep in sync with ShapeKind::param_texels
+	int texels = 0;
+	if (v_kind == KIND_CIRCLE || v_kind == KIND_ROUNDED_RECT) {
+		texels = 5;
+	} else if (v_kind == KIND_SDF_GLYPH) {
+		texels = 2;
+	}
+
+	int first = int(shape >> 4u);
+	vec4 params[5] = vec4[5](vec4(0.0), vec4(0.0), vec4(0.0), vec4(0.0), vec4(0.0));
+	for (int i = 0; i < texels; i++) {
+		params[i] = texelFetch(shape_params, first + i);
+	}
+
+	v_secondary_color = params[0];
+	v_params = params[1];
+	v_extra_params = params[2];
+	v_gradient_color = params[3];
+	v_gradient = params[4];
+}
\ No newline at end of file
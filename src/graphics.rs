@@ -1,12 +1,68 @@
+mod atlas;
 mod backends;
+mod batch;
+mod camera;
+mod path;
+mod sdf;
+mod text;
+mod transform;
+
+pub use self::{
+    camera::Camera2D,
+    path::{
+        LineCap,
+        LineJoin,
+        Path,
+        Polyline,
+        StrokeStyle,
+    },
+    sdf::{
+        TextEffects,
+        TextMode,
+    },
+    text::{
+        Decoration,
+        LayoutOptions,
+        LineLayout,
+        LineMetrics,
+        PlacedGlyph,
+        RichText,
+        TextAlign,
+        TextLayout,
+        TextSpan,
+    },
+    transform::Transform,
+};
 
-use crate::primitives::{
-    Point as SlashPoint,
-    Rect,
+use self::{
+    atlas::{
+        AtlasTarget,
+        GlyphAtlas,
+    },
+    batch::{
+        Batch,
+        EllipseParams,
+        Gradient,
+        RectParams,
+        SdfParams,
+        ShapeKind,
+        Vertex,
+        MAX_VERTICES,
+    },
+    sdf::SdfAtlas,
+};
+use crate::{
+    primitives::{
+        Point as SlashPoint,
+        Rect,
+    },
+    resources::{
+        FontHandle,
+        ResourceManager,
+    },
 };
 
 use gl::types::{
-    GLboolean,
     GLchar,
     GLenum,
     GLfloat,
@@ -15,7 +71,13 @@ use gl::types::{
     GLuint,
 };
 
-use super::AppState;
+use super::{
+    AppState,
+    Backend,
+    ScalingPolicy,
+    SlashError,
+    SlashResult,
+};
 use std::{
     ffi::CString,
     mem,
@@ -23,18 +85,89 @@ use std::{
     str,
 };
 
+/// Where the drawing area ends up inside a surface of `surface_width` by `surface_height`,
+/// and how many drawing units it spans.
+fn fit_view(
+    scaling: ScalingPolicy,
+    design_width: f32,
+    design_height: f32,
+    surface_width: f32,
+    surface_height: f32,
+) -> (Rect<f32>, f32, f32) {
+    let full = Rect::new(0.0, 0.0, surface_width, surface_height);
+
+    match scaling {
+        ScalingPolicy::Stretch => return (full, design_width, design_height),
+        ScalingPolicy::Expand => return (full, surface_width, surface_height),
+        ScalingPolicy::Letterbox => {
+            let scale = (surface_width / design_width).min(surface_height / design_height);
+            let width = (design_width * scale).round();
+            let height = (design_height * scale).round();
+            let x = ((surface_width - width) / 2.0).floor();
+            let y = ((surface_height - height) / 2.0).floor();
+
+            return (Rect::new(x, y, width, height), design_width, design_height);
+        }
+    }
+}
+
 pub struct Graphics {
     pub sprite_renderer: Option<SpriteRenderer>,
+    render_target: Option<Framebuffer>,
+
+    scaling: ScalingPolicy,
+    // The size requested in AppState, kept as the drawing area by Stretch and Letterbox
+    design_width: f32,
+    design_height: f32,
+
+    // The logical size of the surface being drawn into
+    surface_width: f32,
+    surface_height: f32,
+    dpi_factor: f32,
+
+    // The size of the surface in physical pixels
+    width: i32,
+    height: i32,
+
+    viewport: Rect<f32>,
+    view_width: f32,
+    view_height: f32,
 }
 
 impl Graphics {
     pub fn new() -> Self {
         return Graphics {
             sprite_renderer: None,
+            render_target: None,
+
+            scaling: ScalingPolicy::Expand,
+            design_width: 0.0,
+            design_height: 0.0,
+
+            surface_width: 0.0,
+            surface_height: 0.0,
+            dpi_factor: 1.0,
+
+            width: 0,
+            height: 0,
+
+            viewport: Rect::new(0.0, 0.0, 0.0, 0.0),
+            view_width: 0.0,
+            view_height: 0.0,
         };
     }
 
-    pub fn init(&mut self, width: f32, height: f32) {
+    /// `dpi_factor` is the number of physical pixels per logical pixel of the window, see `Window::get_dpi_factor`.
+    pub fn init(&mut self, state: &AppState, dpi_factor: f64) -> SlashResult<()> {
+        let width = state.width as f32;
+        let height = state.height as f32;
+
+        self.dpi_factor = dpi_factor as f32;
+
+        self.scaling = state.scaling;
+        self.design_width = width;
+        self.design_height = height;
+
         unsafe {
             gl::Enable(gl::BLEND);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
@@ -42,26 +175,192 @@ impl Graphics {
             gl::Enable(gl::MULTISAMPLE);
         }
 
-        self.sprite_renderer = Some(SpriteRenderer::new(self, width, height));
+        if let Backend::Headless = state.backend {
+            let mut framebuffer = Framebuffer::new(
+                (width * self.dpi_factor).round() as i32,
+                (height * self.dpi_factor).round() as i32,
+            );
+            if !framebuffer.is_complete() {
+                return Err(SlashError::IncompleteFramebuffer);
+            }
+            framebuffer.enable();
+            self.render_target = Some(framebuffer);
+        }
+
+        self.sprite_renderer = Some(SpriteRenderer::new(self, width, height)?);
+        self.resize(width, height)?;
+
+        return Ok(());
     }
 
-    pub fn clear(&mut self) {
+    /// Adapts the viewport and projection to a surface of the new logical size, following the scaling
+    /// policy. `App` calls this whenever the window is resized.
+    pub fn resize(&mut self, width: f32, height: f32) -> SlashResult<()> {
+        if width < 1.0 || height < 1.0 {
+            // Minimized, keep everything as it was
+            return Ok(());
+        }
+
+        self.flush();
+
+        let pixel_width = (width * self.dpi_factor).round() as i32;
+        let pixel_height = (height * self.dpi_factor).round() as i32;
+
+        let size_changed = self.render_target.as_ref().is_some_and(|target| {
+            (target.get_width(), target.get_height()) != (pixel_width, pixel_height)
+        });
+        if size_changed {
+            let mut framebuffer = Framebuffer::new(pixel_width, pixel_height);
+            if !framebuffer.is_complete() {
+                return Err(SlashError::IncompleteFramebuffer);
+            }
+            framebuffer.enable();
+            self.render_target = Some(framebuffer);
+        }
+
+        let (viewport, view_width, view_height) = fit_view(
+            self.scaling,
+            self.design_width,
+            self.design_height,
+            width,
+            height,
+        );
+
+        unsafe {
+            gl::Viewport(
+                (viewport.x * self.dpi_factor).round() as i32,
+                (viewport.y * self.dpi_factor).round() as i32,
+                (viewport.width * self.dpi_factor).round() as i32,
+                (viewport.height * self.dpi_factor).round() as i32,
+            );
+        }
+
+        if let Some(sprite_renderer) = self.sprite_renderer.as_mut() {
+            sprite_renderer.set_projection(view_width, view_height);
+            sprite_renderer.set_pixel_scale(self.dpi_factor * viewport.width / view_width);
+        }
+
+        self.surface_width = width;
+        self.surface_height = height;
+        self.width = pixel_width;
+        self.height = pixel_height;
+        self.viewport = viewport;
+        self.view_width = view_width;
+        self.view_height = view_height;
+
+        return Ok(());
+    }
+
+    /// Changes the number of physical pixels per logical pixel, keeping the logical size.
+    pub fn set_dpi_factor(&mut self, dpi_factor: f64) -> SlashResult<()> {
+        self.dpi_factor = dpi_factor as f32;
+        let (width, height) = (self.surface_width, self.surface_height);
+        return self.resize(width, height);
+    }
+
+    pub fn get_dpi_factor(&self) -> f64 {
+        return f64::from(self.dpi_factor);
+    }
+
+    /// The width of the drawing area, in drawing units.
+    pub fn get_width(&self) -> f32 {
+        return self.view_width;
+    }
+
+    /// The height of the drawing area, in drawing units.
+    pub fn get_height(&self) -> f32 {
+        return self.view_height;
+    }
+
+    /// The part of the surface that is drawn to, in logical pixels from the bottom left.
+    pub fn get_viewport(&self) -> &Rect<f32> {
+        return &self.viewport;
+    }
+
+    /// Converts a window position, like `Window::mouse_position`, into drawing units.
+    pub fn window_to_view(&self, point: &SlashPoint<f32>) -> SlashPoint<f32> {
+        let x = (point.x - self.viewport.x) * self.view_width / self.viewport.width;
+        let y = (point.y - self.viewport.y) * self.view_height / self.viewport.height;
+        return SlashPoint::new(x, y);
+    }
+
+    /// Converts a window position into the world seen through the sprite renderer's camera, for picking.
+    pub fn window_to_world(&self, point: &SlashPoint<f32>) -> SlashPoint<f32> {
+        let view_point = self.window_to_view(point);
+        return match self.sprite_renderer.as_ref() {
+            Some(sprite_renderer) => sprite_renderer.view_to_world(&view_point),
+            None => view_point,
+        };
+    }
+
+    /// The offscreen framebuffer frames are drawn into, if running headless.
+    pub fn get_render_target(&self) -> Option<&Framebuffer> {
+        return self.render_target.as_ref();
+    }
+
+    /// Reads back the frame currently being drawn (the back buffer, or the offscreen target when headless).
+    /// The returned image is in physical pixels and has its origin in the top left, unlike OpenGL.
+    pub fn capture(&mut self) -> image::RgbaImage {
+        self.flush();
+
+        let width = self.width as u32;
+        let height = self.height as u32;
+        let stride = (width * 4) as usize;
+        let mut data = vec![0u8; stride * height as usize];
+
         unsafe {
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+            if self.render_target.is_none() {
+                gl::ReadBuffer(gl::BACK);
+            } else {
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            }
+
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                data.as_mut_ptr() as *mut _,
+            );
         }
+
+        let flipped: Vec<u8> = data
+            .chunks(stride)
+            .rev()
+            .flat_map(|row| row.iter().cloned())
+            .collect();
+
+        return image::RgbaImage::from_raw(width, height, flipped)
+            .expect("Pixel buffer does not match the framebuffer size");
     }
 
-    //TODO: Result
-    pub fn compile_shader(&self, src: &str, ty: GLenum) -> GLuint {
-        let shader;
+    pub fn clear(&mut self) {
+        if let Some(sprite_renderer) = self.sprite_renderer.as_mut() {
+            sprite_renderer.flush();
+            sprite_renderer.batch.reset_draw_calls();
+            sprite_renderer.reset_clips();
+        }
+
+        // A scissor would limit the clear to part of the frame
         unsafe {
-            shader = gl::CreateShader(ty);
-            let c_str = CString::new(src.as_bytes()).unwrap();
-            gl::ShaderSource(shader, 1, &c_str.as_ptr(), ptr::null());
-            gl::CompileShader(shader);
+            gl::Disable(gl::SCISSOR_TEST);
+            gl::Disable(gl::STENCIL_TEST);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
         }
+    }
+
+    /// Draws anything still batched by the sprite renderer.
+    pub fn flush(&mut self) {
+        if let Some(sprite_renderer) = self.sprite_renderer.as_mut() {
+            sprite_renderer.flush();
+        }
+    }
 
-        return shader;
+    pub fn compile_shader(&self, name: &str, src: &str, ty: GLenum) -> SlashResult<GLuint> {
+        return compile_shader(name, src, ty);
     }
 
     pub fn get_error(&mut self) {
@@ -84,6 +383,53 @@ impl Graphics {
     }
 }
 
+/// Compiles a single shader stage. `name` is only used to identify the source in errors.
+pub fn compile_shader(name: &str, src: &str, ty: GLenum) -> SlashResult<GLuint> {
+    let shader;
+    let c_str = CString::new(src.as_bytes()).map_err(|_| SlashError::ShaderCompile {
+        name: String::from(name),
+        log: String::from("Source contains a nul byte"),
+    })?;
+
+    unsafe {
+        shader = gl::CreateShader(ty);
+        gl::ShaderSource(shader, 1, &c_str.as_ptr(), ptr::null());
+        gl::CompileShader(shader);
+    }
+
+    let mut status = gl::FALSE as GLint;
+
+    unsafe {
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+    }
+
+    if status != (gl::TRUE as GLint) {
+        let mut len: GLint = 0;
+        unsafe {
+            gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
+        }
+
+        let mut buf = vec![0u8; len.max(1) as usize];
+
+        unsafe {
+            gl::GetShaderInfoLog(
+                shader,
+                len,
+                ptr::null_mut(),
+                buf.as_mut_ptr() as *mut GLchar,
+            );
+            gl::DeleteShader(shader);
+        }
+
+        return Err(SlashError::ShaderCompile {
+            name: String::from(name),
+            log: String::from_utf8_lossy(&buf[..buf.len() - 1]).into_owned(),
+        });
+    }
+
+    return Ok(shader);
+}
+
 pub struct VertexArrayObject {
     id: GLuint,
 }
@@ -123,10 +469,23 @@ impl Drop for VertexArrayObject {
 pub enum BufferType {
     Static,
     Dynamic,
+    Stream,
+}
+
+impl BufferType {
+    fn as_gl(&self) -> GLenum {
+        match self {
+            BufferType::Static => gl::STATIC_DRAW,
+            BufferType::Dynamic => gl::DYNAMIC_DRAW,
+            BufferType::Stream => gl::STREAM_DRAW,
+        }
+    }
 }
 
 pub struct VertexBufferObject {
     id: GLuint,
+    size: GLsizeiptr,
+    buffer_type: GLenum,
 }
 
 impl VertexBufferObject {
@@ -137,7 +496,11 @@ impl VertexBufferObject {
             gl::GenBuffers(1, &mut id);
         }
 
-        VertexBufferObject { id }
+        VertexBufferObject {
+            id,
+            size: 0,
+            buffer_type: gl::STATIC_DRAW,
+        }
     }
 
     pub fn enable(&mut self) {
@@ -147,22 +510,44 @@ impl VertexBufferObject {
     }
 
     pub fn set(&mut self, data: &[GLfloat], buffer_type: BufferType) {
-        let data_size: GLsizeiptr = (data.len() * mem::size_of::<GLfloat>()) as GLsizeiptr;
-        let buffer_type = match buffer_type {
-            BufferType::Static => gl::STATIC_DRAW,
-            BufferType::Dynamic => gl::DYNAMIC_DRAW,
-            _ => gl::STATIC_DRAW,
-        };
+        let data_size: GLsizeiptr = mem::size_of_val(data) as GLsizeiptr;
+        self.buffer_type = buffer_type.as_gl();
+        self.size = data_size;
 
         unsafe {
             gl::BufferData(
                 gl::ARRAY_BUFFER,
                 data_size,
-                mem::transmute(&data[0]),
-                buffer_type,
+                data.as_ptr() as *const _,
+                self.buffer_type,
             );
         }
     }
+
+    /// Reserves room for `len` values of `T` without uploading anything.
+    pub fn allocate<T>(&mut self, len: usize, buffer_type: BufferType) {
+        self.buffer_type = buffer_type.as_gl();
+        self.size = (len * mem::size_of::<T>()) as GLsizeiptr;
+
+        unsafe {
+            gl::BufferData(gl::ARRAY_BUFFER, self.size, ptr::null(), self.buffer_type);
+        }
+    }
+
+    /// Replaces the start of an allocated buffer. The old storage is orphaned first,
+    /// so the driver does not have to wait for draws still reading it.
+    pub fn update<T: Copy>(&mut self, data: &[T]) {
+        let data_size: GLsizeiptr = mem::size_of_val(data) as GLsizeiptr;
+        assert!(
+            data_size <= self.size,
+            "Vertex data does not fit the buffer"
+        );
+
+        unsafe {
+            gl::BufferData(gl::ARRAY_BUFFER, self.size, ptr::null(), self.buffer_type);
+            gl::BufferSubData(gl::ARRAY_BUFFER, 0, data_size, data.as_ptr() as *const _);
+        }
+    }
 }
 
 impl Drop for VertexBufferObject {
@@ -195,12 +580,12 @@ impl ElementBufferObject {
     }
 
     pub fn set(&mut self, data: &[GLuint]) {
-        let data_size: GLsizeiptr = (data.len() * mem::size_of::<GLuint>()) as GLsizeiptr;
+        let data_size: GLsizeiptr = mem::size_of_val(data) as GLsizeiptr;
         unsafe {
             gl::BufferData(
                 gl::ELEMENT_ARRAY_BUFFER,
                 data_size,
-                mem::transmute(&data[0]),
+                data.as_ptr() as *const _,
                 gl::STATIC_DRAW,
             );
         }
@@ -215,8 +600,36 @@ impl Drop for ElementBufferObject {
     }
 }
 
+/// The pixel layout of a `Texture`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureFormat {
+    /// One byte per pixel, read as the red channel. Used for glyph coverage.
+    Red,
+    /// Four bytes per pixel.
+    Rgba,
+}
+
+impl TextureFormat {
+    fn as_gl(self) -> GLenum {
+        match self {
+            TextureFormat::Red => gl::RED,
+            TextureFormat::Rgba => gl::RGBA,
+        }
+    }
+
+    fn as_gl_internal(self) -> GLenum {
+        match self {
+            TextureFormat::Red => gl::R8,
+            TextureFormat::Rgba => gl::RGBA8,
+        }
+    }
+}
+
 pub struct Texture {
     id: GLuint,
+    width: i32,
+    height: i32,
+    format: TextureFormat,
 }
 
 impl Texture {
@@ -227,33 +640,78 @@ impl Texture {
             gl::GenTextures(1, &mut id);
         }
 
-        Texture { id }
+        Texture {
+            id,
+            width: 0,
+            height: 0,
+            format: TextureFormat::Rgba,
+        }
     }
 
-    pub fn enable(&mut self) {
+    /// Uploads an image into a new texture with linear filtering and clamped edges.
+    pub fn from_image(image: &image::RgbaImage) -> Self {
+        let mut texture = Texture::new();
+        texture.enable();
+        texture.set(
+            image.width() as i32,
+            image.height() as i32,
+            TextureFormat::Rgba,
+            image,
+        );
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+        }
+
+        return texture;
+    }
+
+    pub fn enable(&self) {
         unsafe {
             gl::BindTexture(gl::TEXTURE_2D, self.id);
         }
     }
 
-    pub fn set(&mut self, width: i32, height: i32, data: &[u8]) {
+    pub fn get_width(&self) -> i32 {
+        return self.width;
+    }
+
+    pub fn get_height(&self) -> i32 {
+        return self.height;
+    }
+
+    pub fn get_format(&self) -> TextureFormat {
+        return self.format;
+    }
+
+    /// Replaces the contents of the currently enabled texture. `data` is tightly packed rows, top row first.
+    pub fn set(&mut self, width: i32, height: i32, format: TextureFormat, data: &[u8]) {
         unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RED as i32,
+                format.as_gl_internal() as i32,
                 width,
                 height,
                 0,
-                gl::RGB,
+                format.as_gl(),
                 gl::UNSIGNED_BYTE,
-                std::mem::transmute(&data[0]),
+                data.as_ptr() as *const _,
             );
         }
+
+        self.width = width;
+        self.height = height;
+        self.format = format;
     }
 
     pub fn update(&mut self, rect: &Rect<f32>, data: &[u8]) {
         unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexSubImage2D(
                 gl::TEXTURE_2D,
                 0,
@@ -261,9 +719,9 @@ impl Texture {
                 rect.y as _,
                 rect.width as _,
                 rect.height as _,
-                gl::RED,
+                self.format.as_gl(),
                 gl::UNSIGNED_BYTE,
-                std::mem::transmute(&data[0]),
+                data.as_ptr() as *const _,
             );
         }
     }
@@ -277,6 +735,90 @@ impl Drop for Texture {
     }
 }
 
+/// An offscreen render target backed by a RGBA8 renderbuffer.
+pub struct Framebuffer {
+    id: GLuint,
+    color_buffer: GLuint,
+    // Depth and stencil, for clip masks
+    stencil_buffer: GLuint,
+    width: i32,
+    height: i32,
+}
+
+impl Framebuffer {
+    pub fn new(width: i32, height: i32) -> Self {
+        let mut id = 0;
+        let mut color_buffer = 0;
+        let mut stencil_buffer = 0;
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut id);
+            gl::GenRenderbuffers(1, &mut color_buffer);
+            gl::GenRenderbuffers(1, &mut stencil_buffer);
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, color_buffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width, height);
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, stencil_buffer);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH24_STENCIL8, width, height);
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, id);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::RENDERBUFFER,
+                color_buffer,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                stencil_buffer,
+            );
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        Framebuffer {
+            id,
+            color_buffer,
+            stencil_buffer,
+            width,
+            height,
+        }
+    }
+
+    pub fn enable(&mut self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+    }
+
+    pub fn get_width(&self) -> i32 {
+        return self.width;
+    }
+
+    pub fn get_height(&self) -> i32 {
+        return self.height;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            return gl::CheckFramebufferStatus(gl::FRAMEBUFFER) == gl::FRAMEBUFFER_COMPLETE;
+        }
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            gl::DeleteRenderbuffers(1, &self.color_buffer);
+            gl::DeleteRenderbuffers(1, &self.stencil_buffer);
+        }
+    }
+}
+
 pub struct ShaderProgram {
     pub id: GLuint,
 }
@@ -288,8 +830,8 @@ impl ShaderProgram {
         }
     }
 
-    pub fn compile(vertex_shader: GLuint, fragment_shader: GLuint) -> Result<Self, String> {
-        let mut shader = ShaderProgram::new();
+    pub fn compile(vertex_shader: GLuint, fragment_shader: GLuint) -> SlashResult<Self> {
+        let shader = ShaderProgram::new();
 
         unsafe {
             gl::AttachShader(shader.id, vertex_shader);
@@ -309,10 +851,9 @@ impl ShaderProgram {
                 gl::GetProgramiv(shader.id, gl::INFO_LOG_LENGTH, &mut len);
             }
 
-            let mut buf = Vec::with_capacity(len as usize);
+            let mut buf = vec![0u8; len.max(1) as usize];
 
             unsafe {
-                buf.set_len((len as usize) - 1);
                 gl::GetProgramInfoLog(
                     shader.id,
                     len,
@@ -321,12 +862,43 @@ impl ShaderProgram {
                 );
             }
 
-            return Err(String::from_utf8(buf).expect("Invalid UTF8"));
+            return Err(SlashError::ShaderLink {
+                log: String::from_utf8_lossy(&buf[..buf.len() - 1]).into_owned(),
+            });
         }
 
         return Ok(shader);
     }
 
+    /// Compiles and links a program straight from GLSL sources. The names identify the sources in errors.
+    pub fn from_sources(
+        vertex_name: &str,
+        vertex_src: &str,
+        fragment_name: &str,
+        fragment_src: &str,
+    ) -> SlashResult<Self> {
+        let vertex_shader = compile_shader(vertex_name, vertex_src, gl::VERTEX_SHADER)?;
+        let fragment_shader = match compile_shader(fragment_name, fragment_src, gl::FRAGMENT_SHADER)
+        {
+            Ok(shader) => shader,
+            Err(e) => {
+                unsafe {
+                    gl::DeleteShader(vertex_shader);
+                }
+                return Err(e);
+            }
+        };
+
+        let program = ShaderProgram::compile(vertex_shader, fragment_shader);
+
+        unsafe {
+            gl::DeleteShader(vertex_shader);
+            gl::DeleteShader(fragment_shader);
+        }
+
+        return program;
+    }
+
     pub fn enable(&self) {
         unsafe {
             gl::UseProgram(self.id);
@@ -351,6 +923,15 @@ impl ShaderProgram {
         }
     }
 
+    pub fn set_int(&self, var: &str, data: i32) {
+        let var_str = CString::new(var).unwrap();
+
+        unsafe {
+            let loc = gl::GetUniformLocation(self.id, var_str.as_ptr());
+            gl::Uniform1i(loc, data);
+        }
+    }
+
     pub fn set_float(&self, var: &str, data: f32) {
         let var_str = CString::new(var).unwrap();
 
@@ -369,256 +950,608 @@ impl Drop for ShaderProgram {
     }
 }
 
-pub struct SpriteRenderer {
-    quad_program: ShaderProgram,
-    quad_vao: VertexArrayObject,
-    quad_vbo: VertexBufferObject,
+/// Identifies a font registered with a `SpriteRenderer`. Doubles as the font id in the glyph cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+// A font registered from a `ResourceManager`, swapped out when the file is reloaded
+struct FontResource {
+    font: FontId,
+    handle: FontHandle,
+    generation: u64,
+}
+
+// What is drawn to while a clip is active
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Clip {
+    // In framebuffer pixels: x, y from the bottom left, width, height
+    scissor: Option<[i32; 4]>,
+    // Pixels inside every active mask hold this stencil value
+    mask_depth: u32,
+}
+
+impl Clip {
+    const NONE: Clip = Clip {
+        scissor: None,
+        mask_depth: 0,
+    };
+}
 
-    circle_program: ShaderProgram,
+/// The pixels inside both scissor rectangles, `a` being unlimited if `None`.
+fn intersect_scissor(a: Option<[i32; 4]>, b: [i32; 4]) -> [i32; 4] {
+    let a = match a {
+        Some(a) => a,
+        None => return b,
+    };
+
+    let x1 = a[0].max(b[0]);
+    let y1 = a[1].max(b[1]);
+    let x2 = (a[0] + a[2]).min(b[0] + b[2]);
+    let y2 = (a[1] + a[3]).min(b[1] + b[3]);
+    return [x1, y1, (x2 - x1).max(0), (y2 - y1).max(0)];
+}
 
-    line_program: ShaderProgram,
-    line_vbo: VertexBufferObject,
+pub struct SpriteRenderer {
+    batch: Batch,
+    // Physical pixels per drawing unit
+    pixel_scale: f32,
 
-    text_vbo: VertexBufferObject,
     text_texture: Texture,
-    text_program: ShaderProgram,
 
-    font: rusttype::Font<'static>,
-    font_cache: rusttype::gpu_cache::Cache<'static>,
-    ortho: nalgebra::base::Matrix4<f32>,
+    fonts: Vec<rusttype::Font<'static>>,
+    font_resources: Vec<FontResource>,
+    glyph_atlas: GlyphAtlas,
+
+    text_mode: TextMode,
+    text_effects: TextEffects,
+    sdf_texture: Texture,
+    sdf_atlas: SdfAtlas,
+
+    // Each entry includes the ones below it
+    transforms: Vec<Transform>,
+    camera: Option<Camera2D>,
+    view_width: f32,
+    view_height: f32,
 
-    test: u32,
+    // Each entry includes the ones below it
+    clips: Vec<Clip>,
 }
 
 impl SpriteRenderer {
-    pub fn new(graphics: &mut Graphics, width: f32, height: f32) -> Self {
-        let quad_vertex_data: [GLfloat; 16] = [
-            0.0, 0.0, -1.0, -1.0, 1.0, 0.0, 1.0, -1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 1.0, -1.0, 1.0,
-        ];
+    pub fn new(graphics: &mut Graphics, width: f32, height: f32) -> SlashResult<Self> {
+        let batch_vs_src = include_str!("batch.vs.glsl");
+        let batch_fs_src = include_str!("batch.fs.glsl");
+        let batch_vs = graphics.compile_shader("batch.vs.glsl", batch_vs_src, gl::VERTEX_SHADER)?;
+        let batch_fs =
+            graphics.compile_shader("batch.fs.glsl", batch_fs_src, gl::FRAGMENT_SHADER)?;
 
-        let mut quad_vao = VertexArrayObject::new();
-        quad_vao.enable();
-        quad_vao.enable_attribute(0);
-        quad_vao.enable_attribute(1);
-        quad_vao.enable_attribute(2);
-        quad_vao.enable_attribute(3);
-        quad_vao.enable_attribute(4);
+        let batch_program = ShaderProgram::compile(batch_vs, batch_fs)?;
 
-        let mut quad_vbo = VertexBufferObject::new();
-        quad_vbo.enable();
-        quad_vbo.set(&quad_vertex_data, BufferType::Static);
+        let batch = Batch::new(batch_program);
 
-        //TODO: VAO or VBO?
         unsafe {
-            gl::VertexAttribPointer(
-                0,
-                2,
-                gl::FLOAT,
-                gl::FALSE as GLboolean,
-                std::mem::size_of::<GLfloat>() as i32 * 4,
-                ptr::null(),
-            );
+            gl::DeleteShader(batch_fs);
+            gl::DeleteShader(batch_vs);
+        }
 
-            gl::VertexAttribPointer(
-                1,
-                2,
-                gl::FLOAT,
-                gl::FALSE as GLboolean,
-                std::mem::size_of::<GLfloat>() as i32 * 4,
-                (std::mem::size_of::<GLfloat>() * 2) as *const _,
-            );
+        let font_data = include_bytes!("./GoudyStMTT.ttf");
+        let font = rusttype::Font::from_bytes(font_data as &[u8])?;
+        let max_atlas_size = max_texture_size().clamp(256, 4096);
+
+        let glyph_atlas = GlyphAtlas::new(max_atlas_size);
+        let (atlas_width, atlas_height) = glyph_atlas.get_dimensions();
+        let text_texture = atlas_texture(atlas_width, atlas_height);
+
+        let sdf_atlas = SdfAtlas::new(max_atlas_size);
+        let (sdf_width, sdf_height) = sdf_atlas.get_dimensions();
+        let sdf_texture = atlas_texture(sdf_width, sdf_height);
+
+        let mut sprite_renderer = SpriteRenderer {
+            batch,
+            pixel_scale: 1.0,
+
+            text_texture,
+
+            fonts: vec![font],
+            font_resources: Vec::new(),
+            glyph_atlas,
+
+            text_mode: TextMode::Bitmap,
+            text_effects: TextEffects::new(),
+            sdf_texture,
+            sdf_atlas,
+
+            transforms: Vec::new(),
+            camera: None,
+            view_width: width,
+            view_height: height,
+
+            clips: Vec::new(),
+        };
+        sprite_renderer.set_projection(width, height);
+
+        return Ok(sprite_renderer);
+    }
+
+    /// Maps drawing coordinates from (0, 0) in the bottom left to (`width`, `height`) in the top right.
+    pub fn set_projection(&mut self, width: f32, height: f32) {
+        self.batch.flush();
+
+        let ortho = nalgebra::Orthographic3::new(0.0, width, 0.0, height, -1.0, 1.0).into_inner();
+
+        let batch_program = self.batch.get_program();
+        batch_program.enable();
+        batch_program.set_uniform_matrix4("Projection", ortho.as_slice());
+
+        // A camera keeps its position at the center of the new size
+        self.view_width = width;
+        self.view_height = height;
+        self.update_transform();
+    }
+
+    /// Transforms everything drawn until the matching `pop_transform`, within the transforms pushed before.
+    /// Text, strokes and anti-aliasing adapt to the scale, so shapes stay sharp when zoomed.
+    pub fn push_transform(&mut self, transform: &Transform) {
+        let combined = self.get_transform().then(transform);
+        self.transforms.push(combined);
+        self.update_transform();
+    }
+
+    /// Goes back to the transform in use before the last `push_transform`. Does nothing if none is left.
+    pub fn pop_transform(&mut self) {
+        self.transforms.pop();
+        self.update_transform();
+    }
+
+    /// All pushed transforms combined, without the camera's.
+    pub fn get_transform(&self) -> Transform {
+        return match self.transforms.last() {
+            Some(transform) => *transform,
+            None => Transform::identity(),
+        };
+    }
+
+    /// Draws the following primitives as seen by `camera`, or straight in view coordinates for `None`.
+    /// The transform stack applies inside the camera's world.
+    pub fn set_camera(&mut self, camera: Option<Camera2D>) {
+        self.camera = camera;
+        self.update_transform();
+    }
+
+    pub fn get_camera(&self) -> Option<&Camera2D> {
+        return self.camera.as_ref();
+    }
+
+    /// The world point under a point of the view, as the camera sees it. Combine with
+    /// `Graphics::window_to_view` to find what the mouse is over.
+    pub fn view_to_world(&self, point: &SlashPoint<f32>) -> SlashPoint<f32> {
+        return match &self.camera {
+            Some(camera) => camera.view_to_world(point, self.view_width, self.view_height),
+            None => *point,
+        };
+    }
+
+    /// Where a world point appears in the view, as the camera sees it.
+    pub fn world_to_view(&self, point: &SlashPoint<f32>) -> SlashPoint<f32> {
+        return match &self.camera {
+            Some(camera) => camera.world_to_view(point, self.view_width, self.view_height),
+            None => *point,
+        };
+    }
+
+    /// Only draws inside `rect` until the matching `pop_clip`, within the clips pushed before. `rect` is
+    /// transformed like anything drawn; if that turns it, it is clipped to with a mask instead of a scissor.
+    pub fn push_clip_rect(&mut self, rect: &Rect<f32>) {
+        let transform = *self.batch.get_transform();
+        if !transform.is_axis_aligned() {
+            let rect = *rect;
+            self.push_clip_mask(|renderer| {
+                renderer.draw_rect(&rect, &Color::from_rgba(255, 255, 255, 255))
+            });
+            return;
         }
 
-        let mut line_vbo = VertexBufferObject::new();
-        line_vbo.enable();
+        let corner1 = transform.transform_point(&SlashPoint::new(rect.x, rect.y));
+        let corner2 = transform.transform_point(&rect.get_upper_right());
 
+        let mut viewport = [0; 4];
         unsafe {
-            gl::VertexAttribPointer(2, 2, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
         }
+        let to_pixels = |x: f32, y: f32| {
+            return (
+                (viewport[0] as f32 + x * viewport[2] as f32 / self.view_width).round() as i32,
+                (viewport[1] as f32 + y * viewport[3] as f32 / self.view_height).round() as i32,
+            );
+        };
+        let (x1, y1) = to_pixels(corner1.x.min(corner2.x), corner1.y.min(corner2.y));
+        let (x2, y2) = to_pixels(corner1.x.max(corner2.x), corner1.y.max(corner2.y));
+
+        let current = self.get_clip();
+        let clip = Clip {
+            scissor: Some(intersect_scissor(
+                current.scissor,
+                [x1, y1, x2 - x1, y2 - y1],
+            )),
+            ..current
+        };
 
-        let mut text_vbo = VertexBufferObject::new();
-        text_vbo.enable();
+        self.batch.flush();
+        self.clips.push(clip);
+        self.apply_clip();
+    }
+
+    /// Only draws where the shapes drawn by `draw_mask` cover, until the matching `pop_clip`, within the
+    /// clips pushed before. The mask shapes themselves are not visible, and count fully wherever they draw
+    /// anything, so their edges are not anti-aliased.
+    pub fn push_clip_mask<F: FnOnce(&mut SpriteRenderer)>(&mut self, draw_mask: F) {
+        let current = self.get_clip();
 
+        // Raises the stencil by one inside the mask and the current clips, once per pixel
+        self.batch.flush();
         unsafe {
-            gl::VertexAttribPointer(
-                3,
-                2,
-                gl::FLOAT,
-                gl::FALSE as GLboolean,
-                std::mem::size_of::<GLfloat>() as i32 * 4,
-                ptr::null(),
-            );
+            gl::Enable(gl::STENCIL_TEST);
+            gl::StencilFunc(gl::EQUAL, current.mask_depth as GLint, 0xFF);
+            gl::StencilOp(gl::KEEP, gl::KEEP, gl::INCR);
+            gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
         }
 
+        draw_mask(self);
+        self.batch.flush();
+
         unsafe {
-            gl::VertexAttribPointer(
-                4,
-                2,
-                gl::FLOAT,
-                gl::FALSE as GLboolean,
-                std::mem::size_of::<GLfloat>() as i32 * 4,
-                (std::mem::size_of::<GLfloat>() * 2) as *const _,
-            );
+            gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
         }
 
-        let quad_vs_src = include_str!("quad.vs.glsl");
-        let quad_fs_src = include_str!("quad.fs.glsl");
-        let quad_vs = graphics.compile_shader(quad_vs_src, gl::VERTEX_SHADER);
-        let quad_fs = graphics.compile_shader(quad_fs_src, gl::FRAGMENT_SHADER);
-        let ortho = nalgebra::Orthographic3::new(0.0, width, 0.0, height, -1.0, 1.0).into_inner();
+        self.clips.push(Clip {
+            mask_depth: current.mask_depth + 1,
+            ..current
+        });
+        self.apply_clip();
+    }
 
-        let quad_program =
-            ShaderProgram::compile(quad_vs, quad_fs).expect("Could not compile shader");
-        quad_program.enable();
-        quad_program.set_uniform_matrix4("Projection", ortho.as_slice());
-        quad_program.set_uniform_vec4("in_color", &vec![0.0, 1.0, 1.0, 1.0]);
+    /// Ends the clip started by the last `push_clip_rect` or `push_clip_mask`.
+    pub fn pop_clip(&mut self) {
+        let clip = match self.clips.pop() {
+            Some(clip) => clip,
+            None => return,
+        };
+        let previous = self.get_clip();
 
-        let circle_vs_src = include_str!("circle.vs.glsl");
-        let circle_fs_src = include_str!("circle.fs.glsl");
-        let circle_vs = graphics.compile_shader(circle_vs_src, gl::VERTEX_SHADER);
-        let circle_fs = graphics.compile_shader(circle_fs_src, gl::FRAGMENT_SHADER);
+        self.batch.flush();
+        if clip.mask_depth > previous.mask_depth {
+            // Lowers the stencil back over the whole view, which is cheaper than drawing the mask again.
+            // The scissor is still the one the mask was drawn within.
+            unsafe {
+                gl::StencilFunc(gl::LESS, previous.mask_depth as GLint, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::REPLACE);
+                gl::ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+            }
 
-        let circle_program =
-            ShaderProgram::compile(circle_vs, circle_fs).expect("Could not compile shader");
-        circle_program.enable();
-        circle_program.set_uniform_matrix4("Projection", ortho.as_slice());
-        circle_program.set_float("border_width", 15.0);
+            let transform = *self.batch.get_transform();
+            self.batch.set_transform(Transform::identity());
+            self.draw_rect(
+                &Rect::new(0.0, 0.0, self.view_width, self.view_height),
+                &Color::from_rgba(255, 255, 255, 255),
+            );
+            self.batch.flush();
+            self.batch.set_transform(transform);
+
+            unsafe {
+                gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+            }
+        }
 
-        let line_vs_src = include_str!("line.vs.glsl");
-        let line_fs_src = include_str!("line.fs.glsl");
-        let line_vs = graphics.compile_shader(line_vs_src, gl::VERTEX_SHADER);
-        let line_fs = graphics.compile_shader(line_fs_src, gl::FRAGMENT_SHADER);
+        self.apply_clip();
+    }
 
-        let mut line_program =
-            ShaderProgram::compile(line_vs, line_fs).expect("Could not compile shader");
-        line_program.enable();
-        line_program.set_uniform_matrix4("Projection", ortho.as_slice());
+    // Drops any clips left over so the next frame starts unclipped
+    fn reset_clips(&mut self) {
+        debug_assert!(
+            self.clips.is_empty(),
+            "{} clips were pushed but never popped",
+            self.clips.len()
+        );
+        self.clips.clear();
+        self.apply_clip();
+    }
 
-        let text_vs_src = include_str!("text.vs.glsl");
-        let text_fs_src = include_str!("text.fs.glsl");
-        let text_vs = graphics.compile_shader(text_vs_src, gl::VERTEX_SHADER);
-        let text_fs = graphics.compile_shader(text_fs_src, gl::FRAGMENT_SHADER);
+    fn get_clip(&self) -> Clip {
+        return match self.clips.last() {
+            Some(clip) => *clip,
+            None => Clip::NONE,
+        };
+    }
 
-        let mut text_program =
-            ShaderProgram::compile(text_vs, text_fs).expect("Could not compile shader");
-        text_program.enable();
-        text_program.set_uniform_matrix4("Projection", ortho.as_slice());
+    // Sets up GL for the current clip. What is batched must have been flushed before it changed.
+    fn apply_clip(&self) {
+        let clip = self.get_clip();
 
         unsafe {
-            gl::DeleteShader(quad_fs);
-            gl::DeleteShader(quad_vs);
-            gl::DeleteShader(circle_fs);
-            gl::DeleteShader(circle_vs);
-            gl::DeleteShader(line_fs);
-            gl::DeleteShader(line_vs);
+            match clip.scissor {
+                Some([x, y, width, height]) => {
+                    gl::Enable(gl::SCISSOR_TEST);
+                    gl::Scissor(x, y, width, height);
+                }
+                None => gl::Disable(gl::SCISSOR_TEST),
+            }
+
+            if clip.mask_depth > 0 {
+                gl::Enable(gl::STENCIL_TEST);
+                gl::StencilFunc(gl::EQUAL, clip.mask_depth as GLint, 0xFF);
+                gl::StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            } else {
+                gl::Disable(gl::STENCIL_TEST);
+            }
         }
+    }
 
-        let font_data = include_bytes!("./GoudyStMTT.ttf");
-        let font = rusttype::Font::from_bytes(font_data as &[u8]).expect("Error loading font");
-        let font_cache = rusttype::gpu_cache::Cache::builder()
-            .dimensions(256, 256)
-            .build();
+    fn update_transform(&mut self) {
+        let view = match &self.camera {
+            Some(camera) => camera.get_transform(self.view_width, self.view_height),
+            None => Transform::identity(),
+        };
+        self.batch.set_transform(view.then(&self.get_transform()));
+    }
 
-        unsafe {
-            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
-        }
+    // Physical pixels per unit of the coordinates currently drawn in
+    fn device_scale(&self) -> f32 {
+        let scale = self.pixel_scale * self.batch.get_transform().get_scale();
+        return scale.max(f32::EPSILON);
+    }
 
-        let mut text_texture = Texture::new();
-        text_texture.enable();
-        text_texture.set(256, 256, &vec![128u8; (256 * 256) as usize]);
+    /// The font built into slash, which is always available.
+    pub fn default_font(&self) -> FontId {
+        return FontId(0);
+    }
 
-        unsafe {
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+    /// Registers a font for drawing. Fonts from a `ResourceManager` should go through `add_font_resource`
+    /// instead, fonts added here stay as they are when their file is reloaded.
+    pub fn add_font(&mut self, font: rusttype::Font<'static>) -> FontId {
+        self.fonts.push(font);
+        return FontId(self.fonts.len() - 1);
+    }
+
+    /// Loads a font through `resources` and registers it for drawing. The font behind the returned id
+    /// follows hot reloads of the file, see `refresh_fonts`.
+    pub fn add_font_resource(
+        &mut self,
+        resources: &mut ResourceManager,
+        handle: FontHandle,
+    ) -> SlashResult<FontId> {
+        let font = self.add_font(resources.load_resource(handle)?.clone());
+        self.font_resources.push(FontResource {
+            font,
+            handle,
+            generation: resources.get_generation(handle),
+        });
+        return Ok(font);
+    }
+
+    /// Swaps in the fonts from `add_font_resource` that were reloaded since the last call, so their ids draw
+    /// the new glyphs. Called once per frame by `App`. Layouts made before a swap still hold the old glyphs.
+    pub fn refresh_fonts(&mut self, resources: &ResourceManager) {
+        let mut swapped = false;
+        for entry in self.font_resources.iter_mut() {
+            let generation = resources.get_generation(entry.handle);
+            if generation == entry.generation {
+                continue;
+            }
+            entry.generation = generation;
+
+            let font = match resources.get_resource(entry.handle) {
+                Some(font) => font.clone(),
+                None => continue,
+            };
+            self.fonts[entry.font.0] = font;
+            swapped = true;
         }
 
-        return SpriteRenderer {
-            quad_program,
-            quad_vao,
-            quad_vbo,
+        if swapped {
+            // Both atlases know glyphs by font id and glyph id, which the new font reuses for other shapes
+            self.glyph_atlas.clear();
+            let mut target = GlyphTarget {
+                batch: &mut self.batch,
+                texture: &mut self.sdf_texture,
+            };
+            self.sdf_atlas.clear(&mut target);
+        }
+    }
 
-            circle_program,
+    /// Loads and registers a TrueType font, or an OpenType font with TrueType outlines.
+    pub fn load_font<P: AsRef<std::path::Path>>(&mut self, path: P) -> SlashResult<FontId> {
+        let data = std::fs::read(path)?;
+        let font = rusttype::Font::from_bytes(data)?;
+        return Ok(self.add_font(font));
+    }
 
-            line_vbo,
-            line_program,
+    pub fn get_font(&self, font: FontId) -> Option<&rusttype::Font<'static>> {
+        return self.fonts.get(font.0);
+    }
 
-            text_vbo,
-            text_texture,
-            text_program,
+    /// The current size of the glyph atlas texture. It starts small and doubles whenever the glyphs
+    /// drawn by one call do not fit.
+    pub fn get_glyph_atlas_size(&self) -> (u32, u32) {
+        return self.glyph_atlas.get_dimensions();
+    }
 
-            font,
-            font_cache,
-            ortho,
-            test: 0,
-        };
+    /// The current size of the distance field atlas used by `TextMode::Sdf`.
+    pub fn get_sdf_atlas_size(&self) -> (u32, u32) {
+        return self.sdf_atlas.get_dimensions();
     }
 
-    pub fn enable_quad(&mut self) {
-        self.quad_vao.enable();
-        self.quad_vbo.enable();
-        self.quad_program.enable();
+    pub fn get_max_glyph_atlas_size(&self) -> u32 {
+        return self.glyph_atlas.get_max_size();
     }
 
-    pub fn enable_circle(&mut self) {
-        self.quad_vao.enable();
-        self.quad_vbo.enable();
-        self.circle_program.enable();
+    /// Limits how large the glyph atlases of both text modes may grow. Defaults to 4096, or less if
+    /// the GL driver cannot handle textures that big. Sizes above the driver's limit are lowered to it.
+    pub fn set_max_glyph_atlas_size(&mut self, max_size: u32) {
+        let max_size = max_size.min(max_texture_size());
+        self.glyph_atlas.set_max_size(max_size);
+        self.sdf_atlas.set_max_size(max_size);
     }
 
-    pub fn enable_line(&mut self) {
-        self.quad_vao.enable();
-        self.line_vbo.enable();
-        self.line_program.enable();
+    /// Picks how the following text draws are rasterized. Defaults to `TextMode::Bitmap`.
+    pub fn set_text_mode(&mut self, mode: TextMode) {
+        self.text_mode = mode;
     }
 
-    pub fn enable_text(&mut self) {
-        self.quad_vao.enable();
-        self.text_vbo.enable();
-        self.text_texture.enable();
-        self.text_program.enable();
+    pub fn get_text_mode(&self) -> TextMode {
+        return self.text_mode;
     }
 
-    pub fn draw_rect(&self, rect: &Rect<f32>, color: &Color) {
-        let translation_mat = self.ortho
-            * nalgebra::base::Matrix4::new_translation(&nalgebra::base::Vector3::new(
-                rect.x, rect.y, 0.0,
-            ));
-        let scale_mat = translation_mat
-            * nalgebra::base::Matrix4::new_nonuniform_scaling(&nalgebra::base::Vector3::new(
-                rect.width,
-                rect.height,
-                0.0,
-            ));
-        self.quad_program
-            .set_uniform_matrix4("Projection", scale_mat.as_slice());
+    /// Outlines and shadows for the following text draws. Only applied in `TextMode::Sdf`.
+    pub fn set_text_effects(&mut self, effects: TextEffects) {
+        self.text_effects = effects;
+    }
 
-        self.quad_program
-            .set_uniform_vec4("in_color", &color.as_float_array());
+    pub fn get_text_effects(&self) -> &TextEffects {
+        return &self.text_effects;
+    }
 
-        unsafe {
-            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
-        }
+    /// Sets how many physical pixels one drawing unit covers, which text is rasterized at.
+    pub fn set_pixel_scale(&mut self, pixel_scale: f32) {
+        self.pixel_scale = pixel_scale;
     }
 
-    pub fn draw_circle(&self, x: f32, y: f32, w: f32, h: f32) {
-        let translation_mat = self.ortho
-            * nalgebra::base::Matrix4::new_translation(&nalgebra::base::Vector3::new(
-                x - w / 2.0,
-                y - h / 2.0,
-                0.0,
-            ));
-        let scale_mat = translation_mat
-            * nalgebra::base::Matrix4::new_nonuniform_scaling(&nalgebra::base::Vector3::new(
-                w, h, 0.0,
-            ));
-        self.circle_program
-            .set_uniform_matrix4("Projection", scale_mat.as_slice());
+    // Everything goes through the batch and needs no setup.
+    // These are kept so callers written against the old per-primitive programs still compile.
 
-        unsafe {
-            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+    #[deprecated(note = "shapes are batched and need no setup, remove the call")]
+    pub fn enable_quad(&mut self) {}
+
+    #[deprecated(note = "shapes are batched and need no setup, remove the call")]
+    pub fn enable_circle(&mut self) {}
+
+    #[deprecated(note = "shapes are batched and need no setup, remove the call")]
+    pub fn enable_line(&mut self) {}
+
+    #[deprecated(note = "text is batched and needs no setup, remove the call")]
+    pub fn enable_text(&mut self) {}
+
+    /// Draws everything batched so far. Called by `Graphics` at the end of every frame.
+    pub fn flush(&mut self) {
+        self.batch.flush();
+    }
+
+    /// The number of draw calls issued since the frame started.
+    pub fn get_draw_calls(&self) -> usize {
+        return self.batch.get_draw_calls();
+    }
+
+    pub fn draw_rect(&mut self, rect: &Rect<f32>, color: &Color) {
+        let x1 = rect.x;
+        let y1 = rect.y;
+        let x2 = rect.x + rect.width;
+        let y2 = rect.y + rect.height;
+
+        let corners = [
+            Vertex::new(x1, y1, 0.0, 0.0),
+            Vertex::new(x2, y1, 0.0, 0.0),
+            Vertex::new(x2, y2, 0.0, 0.0),
+            Vertex::new(x1, y2, 0.0, 0.0),
+        ];
+
+        self.batch
+            .push_quad(&corners, color.as_float_array(), ShapeKind::Solid, None);
+    }
+
+    /// Draws a circle around `center`. See `draw_ellipse`.
+    pub fn draw_circle(&mut self, center: &SlashPoint<f32>, radius: f32, style: &ShapeStyle) {
+        self.draw_ellipse(center, radius, radius, style);
+    }
+
+    /// Draws an axis-aligned ellipse around `center`, anti-aliased at any size. The stroke lies
+    /// inside the edge, so it never grows the ellipse past its radii. Nothing is drawn unless both
+    /// radii are positive.
+    pub fn draw_ellipse(
+        &mut self,
+        center: &SlashPoint<f32>,
+        radius_x: f32,
+        radius_y: f32,
+        style: &ShapeStyle,
+    ) {
+        if radius_x <= 0.0 || radius_y <= 0.0 {
+            return;
         }
+
+        // Room for the anti-aliased edge, which reaches half a pixel outside
+        let margin = 1.0 / self.device_scale();
+        let u = (radius_x + margin) / radius_x;
+        let v = (radius_y + margin) / radius_y;
+
+        let x1 = center.x - radius_x - margin;
+        let y1 = center.y - radius_y - margin;
+        let x2 = center.x + radius_x + margin;
+        let y2 = center.y + radius_y + margin;
+
+        let corners = [
+            Vertex::new(x1, y1, -u, -v),
+            Vertex::new(x2, y1, u, -v),
+            Vertex::new(x2, y2, u, v),
+            Vertex::new(x1, y2, -u, v),
+        ];
+
+        let (fill, gradient) = style.fill.as_gradient(center);
+        let params = EllipseParams {
+            stroke_color: style.stroke.as_float_array(),
+            radius_x,
+            radius_y,
+            stroke_width: style.stroke_width,
+            gradient,
+        };
+        self.batch
+            .push_quad_with_params(&corners, fill, ShapeKind::Circle, None, &params.into());
+    }
+
+    /// Draws `rect` filled and outlined by `style`, anti-aliased. The stroke lies inside the rect.
+    pub fn draw_styled_rect(&mut self, rect: &Rect<f32>, style: &ShapeStyle) {
+        self.draw_rounded_rect(rect, &CornerRadii::all(0.0), style);
+    }
+
+    /// Draws `rect` with rounded corners, filled and outlined by `style`. Radii are limited to half
+    /// the shorter side, and the stroke lies inside the rect.
+    pub fn draw_rounded_rect(&mut self, rect: &Rect<f32>, radii: &CornerRadii, style: &ShapeStyle) {
+        let half_width = rect.width.abs() / 2.0;
+        let half_height = rect.height.abs() / 2.0;
+        let center = SlashPoint::new(rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+        let max_radius = half_width.min(half_height);
+        let clamp_radius = |radius: f32| radius.clamp(0.0, max_radius);
+
+        // Room for the anti-aliased edge, which reaches half a pixel outside
+        let margin = 1.0 / self.device_scale();
+        let u = half_width + margin;
+        let v = half_height + margin;
+
+        let corners = [
+            Vertex::new(center.x - u, center.y - v, -u, -v),
+            Vertex::new(center.x + u, center.y - v, u, -v),
+            Vertex::new(center.x + u, center.y + v, u, v),
+            Vertex::new(center.x - u, center.y + v, -u, v),
+        ];
+
+        let (fill, gradient) = style.fill.as_gradient(&center);
+        let params = RectParams {
+            stroke_color: style.stroke.as_float_array(),
+            radii: [
+                clamp_radius(radii.top_left),
+                clamp_radius(radii.top_right),
+                clamp_radius(radii.bottom_right),
+                clamp_radius(radii.bottom_left),
+            ],
+            half_width,
+            half_height,
+            stroke_width: style.stroke_width,
+            gradient,
+        };
+        self.batch.push_quad_with_params(
+            &corners,
+            fill,
+            ShapeKind::RoundedRect,
+            None,
+            &params.into(),
+        );
     }
 
     pub fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: &Color) {
@@ -643,114 +1576,560 @@ impl SpriteRenderer {
         let x6 = x2 - half_dy;
         let y6 = y2 + half_dx;
 
-        let data: [GLfloat; 8] = [x4, y4, x3, y3, x5, y5, x6, y6];
+        let corners = [
+            Vertex::new(x4, y4, 0.0, 0.0),
+            Vertex::new(x3, y3, 0.0, 0.0),
+            Vertex::new(x5, y5, 0.0, 0.0),
+            Vertex::new(x6, y6, 0.0, 0.0),
+        ];
+
+        self.batch
+            .push_quad(&corners, color.as_float_array(), ShapeKind::Solid, None);
+    }
 
-        self.line_vbo.set(&data, BufferType::Dynamic);
-        self.line_program
-            .set_uniform_vec4("in_color", &color.as_float_array());
+    /// Fills every subpath of `path`, closing the open ones. Concave shapes are fine, but subpaths are
+    /// filled independently, so one inside another does not cut a hole.
+    pub fn fill_path(&mut self, path: &Path, color: &Color) {
+        let polylines = path.flatten(path::TOLERANCE / self.device_scale());
+        let triangles = path::fill(&polylines);
+        self.push_triangles(&triangles, color);
+    }
 
-        unsafe {
-            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+    /// Draws a line along every subpath of `path`.
+    pub fn stroke_path(&mut self, path: &Path, style: &StrokeStyle, color: &Color) {
+        let tolerance = path::TOLERANCE / self.device_scale();
+        let polylines = path.flatten(tolerance);
+        let triangles = path::stroke(&polylines, style, tolerance);
+        self.push_triangles(&triangles, color);
+    }
+
+    fn push_triangles(&mut self, triangles: &[path::Triangle], color: &Color) {
+        let color = color.as_float_array();
+        let vertices: Vec<_> = triangles
+            .iter()
+            .flatten()
+            .map(|point| Vertex::new(point.x, point.y, 0.0, 0.0))
+            .collect();
+
+        // Big paths are split up, whole triangles at a time
+        for chunk in vertices.chunks(MAX_VERTICES / 3 * 3) {
+            self.batch.push(chunk, color, ShapeKind::Solid, None);
         }
     }
 
-    //I don't even know go away
-    pub fn draw_text(&mut self, point: &SlashPoint<f32>, data: &str, size: f32, color: &Color) {
-        let screen_width = 480.0;
-        let screen_height = 360.0;
+    /// Draws the `src_rect` region of `texture` (in texels, top left origin) stretched over `dst_rect`.
+    /// The texture's colors are multiplied by `tint`.
+    pub fn draw_sprite(
+        &mut self,
+        texture: &Texture,
+        src_rect: &Rect<f32>,
+        dst_rect: &Rect<f32>,
+        tint: &Color,
+    ) {
+        let tex_width = texture.get_width() as f32;
+        let tex_height = texture.get_height() as f32;
+
+        let u1 = src_rect.x / tex_width;
+        let u2 = (src_rect.x + src_rect.width) / tex_width;
+        let v_top = src_rect.y / tex_height;
+        let v_bottom = (src_rect.y + src_rect.height) / tex_height;
+
+        let x1 = dst_rect.x;
+        let y1 = dst_rect.y;
+        let x2 = dst_rect.x + dst_rect.width;
+        let y2 = dst_rect.y + dst_rect.height;
+
+        let corners = [
+            Vertex::new(x1, y1, u1, v_bottom),
+            Vertex::new(x2, y1, u2, v_bottom),
+            Vertex::new(x2, y2, u2, v_top),
+            Vertex::new(x1, y2, u1, v_top),
+        ];
+
+        self.batch.push_quad(
+            &corners,
+            tint.as_float_array(),
+            ShapeKind::Texture,
+            Some(texture),
+        );
+    }
+
+    /// Lays out `text` in `font` without drawing it. Text in unknown fonts is left out.
+    pub fn layout_text(
+        &self,
+        font: FontId,
+        text: &str,
+        size: f32,
+        options: &LayoutOptions,
+    ) -> TextLayout {
+        let span = TextSpan::new(text, font, size, Color::from_rgba(255, 255, 255, 255));
+        return TextLayout::new(&self.fonts, &[span], options);
+    }
+
+    /// Lays out every span of `text` as one paragraph without drawing it.
+    pub fn layout_rich_text(&self, text: &RichText, options: &LayoutOptions) -> TextLayout {
+        return TextLayout::new(&self.fonts, &text.spans, options);
+    }
+
+    /// The box `draw_text` would fill, relative to the point passed to it.
+    /// Spans from the font's ascent above the first baseline to its descent below the last one.
+    pub fn measure_text(&self, font: FontId, text: &str, size: f32) -> Rect<f32> {
+        return self
+            .layout_text(font, text, size, &LayoutOptions::new())
+            .get_bounds();
+    }
+
+    /// Draws `data` in `font` with its first baseline starting at `point`. `size` is the height of the
+    /// font in drawing units. Newlines start new lines.
+    pub fn draw_text(
+        &mut self,
+        font: FontId,
+        point: &SlashPoint<f32>,
+        data: &str,
+        size: f32,
+        color: &Color,
+    ) {
+        let layout = self.layout_text(font, data, size, &LayoutOptions::new());
+        self.draw_layout(&layout, point, color);
+    }
+
+    /// Draws text laid out by `layout_text` or `layout_rich_text` with its first baseline starting at
+    /// `point`, all in one color.
+    ///
+    /// Text whose glyphs do not fit the glyph atlas together, even at its maximum size, is split into
+    /// several draw calls. A single glyph larger than the whole atlas is skipped.
+    pub fn draw_layout(&mut self, layout: &TextLayout, point: &SlashPoint<f32>, color: &Color) {
+        let color = color.as_float_array();
+        self.draw_glyphs(layout, point, &|_| color);
+    }
 
-        let x = point.x;
-        let y = screen_height - point.y;
+    /// Draws `text` with its first baseline starting at `point`, each span in its own color.
+    pub fn draw_rich_text(
+        &mut self,
+        text: &RichText,
+        point: &SlashPoint<f32>,
+        options: &LayoutOptions,
+    ) {
+        let layout = self.layout_rich_text(text, options);
+        self.draw_glyphs(&layout, point, &|span| {
+            text.spans[span].color.as_float_array()
+        });
+    }
+
+    fn draw_glyphs(
+        &mut self,
+        layout: &TextLayout,
+        point: &SlashPoint<f32>,
+        span_color: &dyn Fn(usize) -> [f32; 4],
+    ) {
+        match self.text_mode {
+            TextMode::Bitmap => self.draw_bitmap_glyphs(layout, point, span_color),
+            TextMode::Sdf => self.draw_sdf_glyphs(layout, point, span_color),
+        }
 
-        let font = &self.font;
-        let font_cache = &mut self.font_cache;
-        let text_texture = &mut self.text_texture;
+        for decoration in &layout.decorations {
+            let rect = &decoration.rect;
+            let x1 = point.x + rect.x;
+            let y1 = point.y + rect.y;
+            let x2 = x1 + rect.width;
+            let y2 = y1 + rect.height;
+
+            let corners = [
+                Vertex::new(x1, y1, 0.0, 0.0),
+                Vertex::new(x2, y1, 0.0, 0.0),
+                Vertex::new(x2, y2, 0.0, 0.0),
+                Vertex::new(x1, y2, 0.0, 0.0),
+            ];
+
+            self.batch.push_quad(
+                &corners,
+                span_color(decoration.span),
+                ShapeKind::Solid,
+                None,
+            );
+        }
+    }
 
-        let glyphs: Vec<_> = font
-            .layout(
-                data,
-                rusttype::Scale::uniform(size),
-                rusttype::Point { x, y },
-            )
-            .map(|glyph| glyph.standalone())
-            .map(|glyph| {
-                font_cache.queue_glyph(0, glyph.clone());
-                glyph
+    fn draw_bitmap_glyphs(
+        &mut self,
+        layout: &TextLayout,
+        point: &SlashPoint<f32>,
+        span_color: &dyn Fn(usize) -> [f32; 4],
+    ) {
+        // Glyphs are rasterized at the size they cover on screen and scaled back into drawing units,
+        // so text stays sharp on dense displays, under Stretch or Letterbox scaling and when transformed.
+        let scale = self.device_scale();
+
+        let SpriteRenderer {
+            batch,
+            text_texture,
+            fonts,
+            glyph_atlas,
+            ..
+        } = self;
+
+        // Positioned in pixels with y pointing down, like rusttype expects
+        let glyphs: Vec<_> = layout
+            .glyphs
+            .iter()
+            .filter_map(|glyph| {
+                let font = fonts.get(glyph.font.0)?;
+                let positioned = font
+                    .glyph(glyph.id)
+                    .scaled(rusttype::Scale::uniform(glyph.size * scale))
+                    .positioned(rusttype::point(
+                        (point.x + glyph.x) * scale,
+                        -glyph.y * scale,
+                    ));
+                return Some((glyph.span, glyph.font.0, positioned));
             })
             .collect();
 
-        font_cache
-            .cache_queued(|rect, data| {
-                text_texture.update(
-                    &Rect::new(
-                        rect.min.x as f32,
-                        rect.min.y as f32,
-                        rect.width() as f32,
-                        rect.height() as f32,
-                    ),
-                    &data,
-                );
+        let mut target = GlyphTarget {
+            batch,
+            texture: text_texture,
+        };
+        push_glyphs(&mut target, glyph_atlas, &glyphs, point, scale, span_color);
+    }
+
+    fn draw_sdf_glyphs(
+        &mut self,
+        layout: &TextLayout,
+        point: &SlashPoint<f32>,
+        span_color: &dyn Fn(usize) -> [f32; 4],
+    ) {
+        let SpriteRenderer {
+            batch,
+            fonts,
+            text_effects,
+            sdf_texture,
+            sdf_atlas,
+            ..
+        } = self;
+
+        let mut target = GlyphTarget {
+            batch,
+            texture: sdf_texture,
+        };
+
+        // Rasterize everything first, growing the atlas changes the uvs of glyphs found earlier
+        let glyphs: Vec<_> = layout
+            .glyphs
+            .iter()
+            .filter_map(|glyph| {
+                let font = fonts.get(glyph.font.0)?;
+                let sdf_glyph = sdf_atlas.get(glyph.font.0, font, glyph.id, &mut target)?;
+                return Some((glyph, sdf_glyph));
             })
-            .expect("Error updating GPU Texture Cache");
-
-        let mut verts = Vec::new();
-        glyphs.iter().for_each(|g| {
-            if let Ok(Some((uv_rect, screen_rect))) = font_cache.rect_for(0, g) {
-                let origin = rusttype::point(0.0, 0.0);
-
-                let gl_rect = rusttype::Rect {
-                    min: origin
-                        + (rusttype::vector(
-                            screen_rect.min.x as f32 / screen_width - 0.5,
-                            1.0 - screen_rect.min.y as f32 / screen_height - 0.5,
-                        )) * 2.0,
-                    max: origin
-                        + (rusttype::vector(
-                            screen_rect.max.x as f32 / screen_width - 0.5,
-                            1.0 - screen_rect.max.y as f32 / screen_height - 0.5,
-                        )) * 2.0,
-                };
+            .collect();
 
-                let local_verts: [f32; 24] = [
-                    gl_rect.min.x,
-                    gl_rect.max.y,
-                    uv_rect.min.x,
-                    uv_rect.max.y,
-                    gl_rect.min.x,
-                    gl_rect.min.y,
-                    uv_rect.min.x,
-                    uv_rect.min.y,
-                    gl_rect.max.x,
-                    gl_rect.min.y,
-                    uv_rect.max.x,
-                    uv_rect.min.y,
-                    gl_rect.max.x,
-                    gl_rect.min.y,
-                    uv_rect.max.x,
-                    uv_rect.min.y,
-                    gl_rect.max.x,
-                    gl_rect.max.y,
-                    uv_rect.max.x,
-                    uv_rect.max.y,
-                    gl_rect.min.x,
-                    gl_rect.max.y,
-                    uv_rect.min.x,
-                    uv_rect.max.y,
+        let quads: Vec<_> = glyphs
+            .iter()
+            .map(|(glyph, sdf_glyph)| {
+                let uv_rect = sdf_atlas.uv_rect(sdf_glyph);
+                let bounds = &sdf_glyph.bounds;
+                let scale = glyph.size / sdf::SDF_SIZE;
+
+                let x1 = point.x + glyph.x + bounds.min.x * scale;
+                let x2 = point.x + glyph.x + bounds.max.x * scale;
+                let y_top = point.y + glyph.y - bounds.min.y * scale;
+                let y_bottom = point.y + glyph.y - bounds.max.y * scale;
+
+                let corners = [
+                    Vertex::new(x1, y_bottom, uv_rect.min.x, uv_rect.max.y),
+                    Vertex::new(x2, y_bottom, uv_rect.max.x, uv_rect.max.y),
+                    Vertex::new(x2, y_top, uv_rect.max.x, uv_rect.min.y),
+                    Vertex::new(x1, y_top, uv_rect.min.x, uv_rect.min.y),
                 ];
-                verts.extend_from_slice(&local_verts);
+                return (glyph, corners);
+            })
+            .collect();
+
+        let GlyphTarget { batch, texture } = target;
+        let effects = *text_effects;
+        let outline_color = effects.outline_color.as_float_array();
+
+        // Shadows go in their own pass so no shadow covers a neighbouring glyph
+        if effects.has_shadow() {
+            let shadow_color = effects.shadow_color.as_float_array();
+            let offset = &effects.shadow_offset;
+
+            for (glyph, corners) in &quads {
+                let mut corners = *corners;
+                for corner in corners.iter_mut() {
+                    corner.position[0] += offset.x;
+                    corner.position[1] += offset.y;
+                }
+
+                // Drawn as the outline of an invisible fill, so the shadow has the outline's shape
+                let params = SdfParams {
+                    outline_color: shadow_color,
+                    outline_width: sdf::to_field_units(effects.outline_width, glyph.size),
+                    softness: sdf::to_field_units(effects.shadow_softness, glyph.size),
+                };
+                batch.push_quad_with_params(
+                    &corners,
+                    [0.0; 4],
+                    ShapeKind::SdfGlyph,
+                    Some(texture),
+                    &params.into(),
+                );
             }
-        });
+        }
+
+        for (glyph, corners) in &quads {
+            let params = SdfParams {
+                outline_color,
+                outline_width: sdf::to_field_units(effects.outline_width, glyph.size),
+                softness: 0.0,
+            };
+            batch.push_quad_with_params(
+                corners,
+                span_color(glyph.span),
+                ShapeKind::SdfGlyph,
+                Some(texture),
+                &params.into(),
+            );
+        }
+    }
+}
 
-        self.text_vbo.set(&verts, BufferType::Dynamic);
-        self.text_program
-            .set_uniform_vec4("in_color", &color.as_float_array());
+// The largest width and height the GL driver accepts for a texture
+fn max_texture_size() -> u32 {
+    let mut max_texture_size = 0;
+    unsafe {
+        gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_texture_size);
+    }
+    return max_texture_size as u32;
+}
 
-        unsafe {
-            gl::DrawArrays(gl::TRIANGLES, 0, verts.len() as i32 / 4);
+// A single channel texture for one of the glyph atlases
+fn atlas_texture(width: u32, height: u32) -> Texture {
+    let mut texture = Texture::new();
+    texture.enable();
+    texture.set(
+        width as i32,
+        height as i32,
+        TextureFormat::Red,
+        &vec![0u8; (width * height) as usize],
+    );
+
+    unsafe {
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+    }
+
+    return texture;
+}
+
+// Lets a glyph atlas write into the text texture, drawing batched text before it changes
+struct GlyphTarget<'a> {
+    batch: &'a mut Batch,
+    texture: &'a mut Texture,
+}
+
+impl<'a> AtlasTarget for GlyphTarget<'a> {
+    fn before_change(&mut self) {
+        self.batch.flush();
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.texture.enable();
+        self.texture.set(
+            width as i32,
+            height as i32,
+            TextureFormat::Red,
+            &vec![0u8; (width * height) as usize],
+        );
+    }
+
+    fn upload(&mut self, rect: rusttype::Rect<u32>, data: &[u8]) {
+        self.texture.enable();
+        self.texture.update(
+            &Rect::new(
+                rect.min.x as f32,
+                rect.min.y as f32,
+                rect.width() as f32,
+                rect.height() as f32,
+            ),
+            data,
+        );
+    }
+}
+
+/// Caches `glyphs` (span, font id, glyph) and batches them, halving the run until each part fits the atlas.
+fn push_glyphs(
+    target: &mut GlyphTarget,
+    atlas: &mut GlyphAtlas,
+    glyphs: &[(usize, usize, rusttype::PositionedGlyph<'static>)],
+    point: &SlashPoint<f32>,
+    scale: f32,
+    span_color: &dyn Fn(usize) -> [f32; 4],
+) {
+    if glyphs.is_empty() {
+        return;
+    }
+
+    let queue: Vec<_> = glyphs
+        .iter()
+        .map(|(_, font_id, glyph)| (*font_id, glyph.clone()))
+        .collect();
+
+    if !atlas.cache(&queue, target) {
+        if glyphs.len() > 1 {
+            // Caching the second half flushes the first before evicting anything it uses
+            let (first, second) = glyphs.split_at(glyphs.len() / 2);
+            push_glyphs(target, atlas, first, point, scale, span_color);
+            push_glyphs(target, atlas, second, point, scale, span_color);
         }
+        return;
+    }
+
+    for (span, font_id, glyph) in glyphs {
+        if let Some((uv_rect, screen_rect)) = atlas.rect_for(*font_id, glyph) {
+            let x1 = screen_rect.min.x as f32 / scale;
+            let x2 = screen_rect.max.x as f32 / scale;
+            let y_top = point.y - screen_rect.min.y as f32 / scale;
+            let y_bottom = point.y - screen_rect.max.y as f32 / scale;
+
+            let corners = [
+                Vertex::new(x1, y_bottom, uv_rect.min.x, uv_rect.max.y),
+                Vertex::new(x2, y_bottom, uv_rect.max.x, uv_rect.max.y),
+                Vertex::new(x2, y_top, uv_rect.max.x, uv_rect.min.y),
+                Vertex::new(x1, y_top, uv_rect.min.x, uv_rect.min.y),
+            ];
+
+            target.batch.push_quad(
+                &corners,
+                span_color(*span),
+                ShapeKind::Glyph,
+                Some(target.texture),
+            );
+        }
+    }
+}
+
+/// What the inside of a shape is filled with. Positions are in drawing coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Paint {
+    Solid(Color),
+    /// Blends from `start_color` at `start` to `end_color` at `end`, and stays constant past them.
+    LinearGradient {
+        start: SlashPoint<f32>,
+        end: SlashPoint<f32>,
+        start_color: Color,
+        end_color: Color,
+    },
+    /// Blends from `inner_color` at `center` to `outer_color` at `radius` and beyond.
+    RadialGradient {
+        center: SlashPoint<f32>,
+        radius: f32,
+        inner_color: Color,
+        outer_color: Color,
+    },
+}
+
+impl Paint {
+    // The vertex color and the gradient for a shape around `center`
+    fn as_gradient(&self, center: &SlashPoint<f32>) -> ([f32; 4], Gradient) {
+        let local = |point: &SlashPoint<f32>| [point.x - center.x, point.y - center.y];
+
+        return match self {
+            Paint::Solid(color) => (color.as_float_array(), Gradient::None),
+            Paint::LinearGradient {
+                start,
+                end,
+                start_color,
+                end_color,
+            } => (
+                start_color.as_float_array(),
+                Gradient::Linear {
+                    start: local(start),
+                    end: local(end),
+                    end_color: end_color.as_float_array(),
+                },
+            ),
+            Paint::RadialGradient {
+                center: gradient_center,
+                radius,
+                inner_color,
+                outer_color,
+            } => (
+                inner_color.as_float_array(),
+                Gradient::Radial {
+                    center: local(gradient_center),
+                    radius: *radius,
+                    end_color: outer_color.as_float_array(),
+                },
+            ),
+        };
+    }
+}
+
+impl From<Color> for Paint {
+    fn from(color: Color) -> Self {
+        return Paint::Solid(color);
     }
 }
 
+/// The radius of each corner of a rounded rectangle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CornerRadii {
+    pub top_left: f32,
+    pub top_right: f32,
+    pub bottom_right: f32,
+    pub bottom_left: f32,
+}
+
+impl CornerRadii {
+    pub fn new(top_left: f32, top_right: f32, bottom_right: f32, bottom_left: f32) -> Self {
+        return CornerRadii {
+            top_left,
+            top_right,
+            bottom_right,
+            bottom_left,
+        };
+    }
+
+    pub fn all(radius: f32) -> Self {
+        return CornerRadii::new(radius, radius, radius, radius);
+    }
+}
+
+/// How a shape is filled and outlined. Both are transparent until set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeStyle {
+    pub fill: Paint,
+    pub stroke: Color,
+    /// In drawing units.
+    pub stroke_width: f32,
+}
+
+impl ShapeStyle {
+    pub fn new() -> Self {
+        return ShapeStyle {
+            fill: Paint::Solid(Color::from_rgba(0, 0, 0, 0)),
+            stroke: Color::from_rgba(0, 0, 0, 0),
+            stroke_width: 0.0,
+        };
+    }
+
+    /// A color or a gradient.
+    pub fn fill<P: Into<Paint>>(mut self, paint: P) -> Self {
+        self.fill = paint.into();
+        self
+    }
+
+    pub fn stroke(mut self, color: Color, width: f32) -> Self {
+        self.stroke = color;
+        self.stroke_width = width;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -772,3 +2151,50 @@ impl Color {
         ];
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretch_and_expand_fill_the_surface() {
+        let (viewport, width, height) =
+            fit_view(ScalingPolicy::Stretch, 320.0, 240.0, 800.0, 400.0);
+        assert_eq!((viewport.width, viewport.height), (800.0, 400.0));
+        assert_eq!((width, height), (320.0, 240.0));
+
+        let (viewport, width, height) = fit_view(ScalingPolicy::Expand, 320.0, 240.0, 800.0, 400.0);
+        assert_eq!((viewport.x, viewport.y), (0.0, 0.0));
+        assert_eq!((width, height), (800.0, 400.0));
+    }
+
+    #[test]
+    fn nested_scissors_intersect() {
+        assert_eq!(intersect_scissor(None, [1, 2, 3, 4]), [1, 2, 3, 4]);
+        assert_eq!(
+            intersect_scissor(Some([0, 0, 100, 50]), [60, 20, 100, 100]),
+            [60, 20, 40, 30]
+        );
+        assert_eq!(
+            intersect_scissor(Some([0, 0, 10, 10]), [20, 20, 5, 5]),
+            [20, 20, 0, 0]
+        );
+    }
+
+    #[test]
+    fn letterbox_keeps_the_aspect_ratio_centered() {
+        let (viewport, width, height) =
+            fit_view(ScalingPolicy::Letterbox, 320.0, 240.0, 800.0, 400.0);
+        assert_eq!(
+            (viewport.x, viewport.y, viewport.width, viewport.height),
+            (133.0, 0.0, 533.0, 400.0)
+        );
+        assert_eq!((width, height), (320.0, 240.0));
+
+        let (viewport, _, _) = fit_view(ScalingPolicy::Letterbox, 320.0, 240.0, 320.0, 480.0);
+        assert_eq!(
+            (viewport.x, viewport.y, viewport.width, viewport.height),
+            (0.0, 120.0, 320.0, 240.0)
+        );
+    }
+}
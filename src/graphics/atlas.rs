@@ -0,0 +1,199 @@
+//! The glyph atlas text is drawn from, grown on demand when glyphs stop fitting.
+
+use rusttype::{
+    gpu_cache::Cache,
+    PositionedGlyph,
+    Rect,
+};
+
+/// Size the atlas starts out at.
+pub const INITIAL_SIZE: u32 = 256;
+
+/// Where the atlas pixels live. Implemented over the GL texture by `SpriteRenderer`.
+pub trait AtlasTarget {
+    /// Called once before the atlas contents change, while the old contents are still intact.
+    fn before_change(&mut self);
+    /// The atlas was resized and cleared.
+    fn resize(&mut self, width: u32, height: u32);
+    fn upload(&mut self, rect: Rect<u32>, data: &[u8]);
+}
+
+pub struct GlyphAtlas {
+    cache: Cache<'static>,
+    max_size: u32,
+}
+
+impl GlyphAtlas {
+    pub fn new(max_size: u32) -> Self {
+        let size = INITIAL_SIZE.min(max_size);
+        return GlyphAtlas {
+            cache: Cache::builder().dimensions(size, size).build(),
+            max_size,
+        };
+    }
+
+    pub fn get_dimensions(&self) -> (u32, u32) {
+        return self.cache.dimensions();
+    }
+
+    pub fn get_max_size(&self) -> u32 {
+        return self.max_size;
+    }
+
+    pub fn set_max_size(&mut self, max_size: u32) {
+        self.max_size = max_size;
+    }
+
+    /// Makes sure all of `glyphs` are in the atlas, doubling its size until they fit.
+    /// Returns false if they do not fit together even at the maximum size.
+    pub fn cache(
+        &mut self,
+        glyphs: &[(usize, PositionedGlyph<'static>)],
+        target: &mut dyn AtlasTarget,
+    ) -> bool {
+        let mut changed = false;
+
+        loop {
+            for (font_id, glyph) in glyphs {
+                self.cache.queue_glyph(*font_id, glyph.clone());
+            }
+
+            let result = self.cache.cache_queued(|rect, data| {
+                if !changed {
+                    target.before_change();
+                    changed = true;
+                }
+                target.upload(rect, data);
+            });
+
+            if result.is_ok() {
+                return true;
+            }
+
+            // A failed write leaves the queue as it was
+            self.cache.clear_queue();
+
+            let (width, height) = self.cache.dimensions();
+            if width >= self.max_size && height >= self.max_size {
+                return false;
+            }
+
+            if !changed {
+                target.before_change();
+                changed = true;
+            }
+
+            let width = (width * 2).min(self.max_size);
+            let height = (height * 2).min(self.max_size);
+            self.cache
+                .to_builder()
+                .dimensions(width, height)
+                .rebuild(&mut self.cache);
+            target.resize(width, height);
+        }
+    }
+
+    /// Forgets every cached glyph, they are rasterized again when next drawn.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Where a glyph cached by the last call to `cache` is, as uvs and as a pixel rect.
+    pub fn rect_for(
+        &self,
+        font_id: usize,
+        glyph: &PositionedGlyph<'static>,
+    ) -> Option<(Rect<f32>, Rect<i32>)> {
+        return self
+            .cache
+            .rect_for(font_id, glyph)
+            .ok()
+            .and_then(|rect| rect);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusttype::{
+        point,
+        Font,
+        Scale,
+    };
+
+    #[derive(Default)]
+    struct FakeTarget {
+        changes: usize,
+        resizes: Vec<(u32, u32)>,
+        uploads: usize,
+    }
+
+    impl AtlasTarget for FakeTarget {
+        fn before_change(&mut self) {
+            self.changes += 1;
+        }
+
+        fn resize(&mut self, width: u32, height: u32) {
+            self.resizes.push((width, height));
+        }
+
+        fn upload(&mut self, _rect: Rect<u32>, _data: &[u8]) {
+            self.uploads += 1;
+        }
+    }
+
+    fn glyphs(text: &str, size: f32) -> Vec<(usize, PositionedGlyph<'static>)> {
+        let font = Font::from_bytes(&include_bytes!("../GoudyStMTT.ttf")[..]).unwrap();
+        return font
+            .layout(text, Scale::uniform(size), point(0.0, 0.0))
+            .map(|glyph| (0, glyph.standalone()))
+            .collect();
+    }
+
+    #[test]
+    fn small_text_fits_without_growing() {
+        let mut atlas = GlyphAtlas::new(4096);
+        let mut target = FakeTarget::default();
+        let glyphs = glyphs("abc", 16.0);
+
+        assert!(atlas.cache(&glyphs, &mut target));
+        assert_eq!(atlas.get_dimensions(), (INITIAL_SIZE, INITIAL_SIZE));
+        assert_eq!(target.changes, 1);
+        assert_eq!(target.uploads, 3);
+        assert!(atlas.rect_for(0, &glyphs[0].1).is_some());
+
+        // Already cached, nothing changes
+        assert!(atlas.cache(&glyphs, &mut target));
+        assert_eq!(target.changes, 1);
+    }
+
+    const ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+    #[test]
+    fn large_glyphs_grow_the_atlas() {
+        let mut atlas = GlyphAtlas::new(4096);
+        let mut target = FakeTarget::default();
+        let glyphs = glyphs(ALPHABET, 100.0);
+
+        assert!(atlas.cache(&glyphs, &mut target));
+        assert!(atlas.get_dimensions().0 > INITIAL_SIZE);
+        assert_eq!(target.resizes.last(), Some(&atlas.get_dimensions()));
+        assert_eq!(target.changes, 1);
+        assert!(glyphs
+            .iter()
+            .all(|(font_id, glyph)| atlas.rect_for(*font_id, glyph).is_some()));
+    }
+
+    #[test]
+    fn glyphs_beyond_the_maximum_size_fail() {
+        let mut atlas = GlyphAtlas::new(256);
+        let mut target = FakeTarget::default();
+
+        assert!(!atlas.cache(&glyphs(ALPHABET, 100.0), &mut target));
+        assert_eq!(atlas.get_dimensions(), (256, 256));
+        assert!(target.resizes.is_empty());
+
+        // The atlas still works for what does fit
+        assert!(atlas.cache(&glyphs("ABC", 100.0), &mut target));
+    }
+}
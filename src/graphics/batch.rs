@@ -0,0 +1,737 @@
+use super::{
+    transform::Transform,
+    BufferType,
+    ShaderProgram,
+    Texture,
+    VertexArrayObject,
+    VertexBufferObject,
+};
+use gl::types::{
+    GLboolean,
+    GLfloat,
+    GLsizeiptr,
+    GLuint,
+};
+use std::{
+    mem,
+    ptr,
+};
+
+/// What the batch fragment shader does with a vertex. Must match `batch.fs.glsl`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShapeKind {
+    /// Flat vertex color.
+    Solid = 0,
+    /// An ellipse filling the unit square of the uvs, which range from -1 to 1 at its edge.
+    /// See `EllipseParams`.
+    Circle = 1,
+    /// RGBA texture multiplied by the vertex color.
+    Texture = 2,
+    /// Vertex color with the alpha taken from the red channel of a glyph atlas.
+    Glyph = 3,
+    /// A glyph from a signed distance field atlas, see `SdfParams`.
+    SdfGlyph = 4,
+    /// A rectangle with rounded corners, with uvs relative to its center in drawing units.
+    /// See `RectParams`.
+    RoundedRect = 5,
+}
+
+impl ShapeKind {
+    /// How many texels of `ShapeParams` the shader reads for this kind. Must match `batch.vs.glsl`.
+    fn param_texels(self) -> usize {
+        return match self {
+            ShapeKind::Circle | ShapeKind::RoundedRect => 5,
+            ShapeKind::SdfGlyph => 2,
+            _ => 0,
+        };
+    }
+}
+
+/// Number of vertices buffered before the batch is flushed on its own.
+pub const MAX_VERTICES: usize = 6 * 8192;
+
+/// Number of RGBA float texels of shape parameters buffered before the batch is flushed on its own.
+/// The smallest texture buffer GL 3.3 allows.
+pub const MAX_PARAM_TEXELS: usize = 65536;
+
+/// A vertex as the shader reads it, 24 bytes. `shape` holds the `ShapeKind` in its low 4 bits and
+/// the index of the shape's first parameter texel above them.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PackedVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [u8; 4],
+    pub shape: u32,
+}
+
+/// A corner of a batched primitive.
+#[derive(Debug, Clone, Copy)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+}
+
+impl Vertex {
+    pub fn new(x: f32, y: f32, u: f32, v: f32) -> Self {
+        Vertex {
+            position: [x, y],
+            uv: [u, v],
+        }
+    }
+}
+
+/// Extra inputs for shape kinds that need more than one color, stored once per shape rather than
+/// per vertex. What they mean depends on the kind, except that `extra[3]` always selects the gradient.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeParams {
+    pub color: [f32; 4],
+    pub values: [f32; 4],
+    pub extra: [f32; 4],
+    pub gradient_color: [f32; 4],
+    pub gradient: [f32; 4],
+}
+
+impl ShapeParams {
+    pub const NONE: ShapeParams = ShapeParams {
+        color: [0.0; 4],
+        values: [0.0; 4],
+        extra: [0.0; 4],
+        gradient_color: [0.0; 4],
+        gradient: [0.0; 4],
+    };
+
+    fn as_texels(&self) -> [[f32; 4]; 5] {
+        return [
+            self.color,
+            self.values,
+            self.extra,
+            self.gradient_color,
+            self.gradient,
+        ];
+    }
+}
+
+/// How the fill of an ellipse or rounded rectangle blends from the vertex color into another one.
+/// Positions are relative to the shape's center, in drawing units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gradient {
+    None,
+    /// The vertex color at `start`, `end_color` at `end`, and constant past them.
+    Linear {
+        start: [f32; 2],
+        end: [f32; 2],
+        end_color: [f32; 4],
+    },
+    /// The vertex color at `center`, `end_color` from `radius` on.
+    Radial {
+        center: [f32; 2],
+        radius: f32,
+        end_color: [f32; 4],
+    },
+}
+
+impl Gradient {
+    // Mode, color and geometry, must match fill_color in `batch.fs.glsl`
+    fn as_params(&self) -> (f32, [f32; 4], [f32; 4]) {
+        return match *self {
+            Gradient::None => (0.0, [0.0; 4], [0.0; 4]),
+            Gradient::Linear {
+                start,
+                end,
+                end_color,
+            } => (1.0, end_color, [start[0], start[1], end[0], end[1]]),
+            Gradient::Radial {
+                center,
+                radius,
+                end_color,
+            } => (2.0, end_color, [center[0], center[1], radius, 0.0]),
+        };
+    }
+}
+
+/// For `ShapeKind::Circle`, with the fill in the vertex color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EllipseParams {
+    pub stroke_color: [f32; 4],
+    pub radius_x: f32,
+    pub radius_y: f32,
+    /// How far the stroke reaches inside the edge, in drawing units.
+    pub stroke_width: f32,
+    pub gradient: Gradient,
+}
+
+impl From<EllipseParams> for ShapeParams {
+    fn from(params: EllipseParams) -> Self {
+        let (mode, gradient_color, gradient) = params.gradient.as_params();
+        return ShapeParams {
+            color: params.stroke_color,
+            values: [params.radius_x, params.radius_y, params.stroke_width, 0.0],
+            extra: [0.0, 0.0, 0.0, mode],
+            gradient_color,
+            gradient,
+        };
+    }
+}
+
+/// For `ShapeKind::RoundedRect`, with the fill in the vertex color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RectParams {
+    pub stroke_color: [f32; 4],
+    /// Top left, top right, bottom right and bottom left, at most half the shorter side.
+    pub radii: [f32; 4],
+    pub half_width: f32,
+    pub half_height: f32,
+    /// How far the stroke reaches inside the edge, in drawing units.
+    pub stroke_width: f32,
+    pub gradient: Gradient,
+}
+
+impl From<RectParams> for ShapeParams {
+    fn from(params: RectParams) -> Self {
+        let (mode, gradient_color, gradient) = params.gradient.as_params();
+        return ShapeParams {
+            color: params.stroke_color,
+            values: params.radii,
+            extra: [
+                params.half_width,
+                params.half_height,
+                params.stroke_width,
+                mode,
+            ],
+            gradient_color,
+            gradient,
+        };
+    }
+}
+
+/// For `ShapeKind::SdfGlyph`, with distances in the atlas' units where 0.5 is the glyph's edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SdfParams {
+    pub outline_color: [f32; 4],
+    /// How far outside the edge the outline reaches.
+    pub outline_width: f32,
+    /// How far the edge is blurred, on top of the pixel wide anti-aliasing.
+    pub softness: f32,
+}
+
+impl From<SdfParams> for ShapeParams {
+    fn from(params: SdfParams) -> Self {
+        return ShapeParams {
+            color: params.outline_color,
+            values: [params.outline_width, params.softness, 0.0, 0.0],
+            ..ShapeParams::NONE
+        };
+    }
+}
+
+/// CPU side of the batch: vertices waiting to be drawn, the parameters of their shapes and the
+/// texture they sample.
+pub struct BatchBuffer {
+    vertices: Vec<PackedVertex>,
+    params: Vec<[f32; 4]>,
+    texture: Option<GLuint>,
+    // Applied to positions as they are added, so changing it does not need a flush
+    transform: Transform,
+}
+
+impl BatchBuffer {
+    pub fn new() -> Self {
+        BatchBuffer {
+            vertices: Vec::with_capacity(MAX_VERTICES),
+            params: Vec::with_capacity(MAX_PARAM_TEXELS),
+            texture: None,
+            transform: Transform::identity(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.vertices.is_empty();
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        return self.vertices.len();
+    }
+
+    pub fn get_texture(&self) -> Option<GLuint> {
+        return self.texture;
+    }
+
+    pub fn get_transform(&self) -> &Transform {
+        return &self.transform;
+    }
+
+    /// Maps the positions of the vertices pushed from now on. Uvs are left alone.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// Whether `count` more vertices of a `kind` shape sampling `texture` can be added without
+    /// drawing what is buffered first.
+    pub fn fits(&self, count: usize, kind: ShapeKind, texture: Option<GLuint>) -> bool {
+        if self.vertices.len() + count > MAX_VERTICES {
+            return false;
+        }
+
+        if self.params.len() + kind.param_texels() > MAX_PARAM_TEXELS {
+            return false;
+        }
+
+        return match (self.texture, texture) {
+            (Some(current), Some(texture)) => current == texture,
+            _ => true,
+        };
+    }
+
+    /// Adds triangles, three vertices each, sharing one set of `params`. Callers must check `fits` first.
+    pub fn push(
+        &mut self,
+        vertices: &[Vertex],
+        color: [f32; 4],
+        kind: ShapeKind,
+        texture: Option<GLuint>,
+        params: &ShapeParams,
+    ) {
+        if texture.is_some() {
+            self.texture = texture;
+        }
+
+        let texels = kind.param_texels();
+        let shape = kind as u32 | (self.params.len() as u32) << 4;
+        self.params.extend_from_slice(&params.as_texels()[..texels]);
+
+        let color = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+        let transform = &self.transform;
+        for vertex in vertices {
+            let [x, y] = vertex.position;
+            self.vertices.push(PackedVertex {
+                position: [
+                    transform.a * x + transform.c * y + transform.tx,
+                    transform.b * x + transform.d * y + transform.ty,
+                ],
+                uv: vertex.uv,
+                color,
+                shape,
+            });
+        }
+    }
+
+    pub fn as_slice(&self) -> &[PackedVertex] {
+        return &self.vertices;
+    }
+
+    pub fn get_params(&self) -> &[[f32; 4]] {
+        return &self.params;
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.params.clear();
+        self.texture = None;
+    }
+}
+
+/// Shape parameters as the shader reads them: a buffer of RGBA float texels on texture unit 1.
+struct ParamsBuffer {
+    buffer: GLuint,
+    texture: GLuint,
+}
+
+impl ParamsBuffer {
+    fn new() -> Self {
+        let mut buffer = 0;
+        let mut texture = 0;
+
+        unsafe {
+            gl::GenBuffers(1, &mut buffer);
+            gl::BindBuffer(gl::TEXTURE_BUFFER, buffer);
+            gl::BufferData(
+                gl::TEXTURE_BUFFER,
+                (MAX_PARAM_TEXELS * mem::size_of::<[f32; 4]>()) as GLsizeiptr,
+                ptr::null(),
+                gl::STREAM_DRAW,
+            );
+
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_BUFFER, texture);
+            gl::TexBuffer(gl::TEXTURE_BUFFER, gl::RGBA32F, buffer);
+            gl::BindTexture(gl::TEXTURE_BUFFER, 0);
+            gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+        }
+
+        ParamsBuffer { buffer, texture }
+    }
+
+    /// Uploads `params` and binds them for the next draw, leaving texture unit 0 active.
+    fn update(&mut self, params: &[[f32; 4]]) {
+        unsafe {
+            gl::BindBuffer(gl::TEXTURE_BUFFER, self.buffer);
+            if !params.is_empty() {
+                gl::BufferData(
+                    gl::TEXTURE_BUFFER,
+                    (MAX_PARAM_TEXELS * mem::size_of::<[f32; 4]>()) as GLsizeiptr,
+                    ptr::null(),
+                    gl::STREAM_DRAW,
+                );
+                gl::BufferSubData(
+                    gl::TEXTURE_BUFFER,
+                    0,
+                    mem::size_of_val(params) as GLsizeiptr,
+                    params.as_ptr() as *const _,
+                );
+            }
+            gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_BUFFER, self.texture);
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+}
+
+impl Drop for ParamsBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+    }
+}
+
+/// Accumulates primitives into one streaming vertex buffer and draws them with as few draw calls as possible.
+/// A flush happens when the buffer is full, when a primitive needs a different texture, or when asked to.
+pub struct Batch {
+    vao: VertexArrayObject,
+    vbo: VertexBufferObject,
+    params: ParamsBuffer,
+    program: ShaderProgram,
+    buffer: BatchBuffer,
+
+    draw_calls: usize,
+}
+
+impl Batch {
+    pub fn new(program: ShaderProgram) -> Self {
+        let mut vao = VertexArrayObject::new();
+        vao.enable();
+
+        let mut vbo = VertexBufferObject::new();
+        vbo.enable();
+        vbo.allocate::<PackedVertex>(MAX_VERTICES, BufferType::Stream);
+
+        let stride = mem::size_of::<PackedVertex>() as i32;
+        for location in 0..4 {
+            vao.enable_attribute(location);
+        }
+
+        unsafe {
+            gl::VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE as GLboolean, stride, ptr::null());
+            gl::VertexAttribPointer(
+                1,
+                2,
+                gl::FLOAT,
+                gl::FALSE as GLboolean,
+                stride,
+                (2 * mem::size_of::<GLfloat>()) as *const _,
+            );
+            gl::VertexAttribPointer(
+                2,
+                4,
+                gl::UNSIGNED_BYTE,
+                gl::TRUE as GLboolean,
+                stride,
+                (4 * mem::size_of::<GLfloat>()) as *const _,
+            );
+            gl::VertexAttribIPointer(
+                3,
+                1,
+                gl::UNSIGNED_INT,
+                stride,
+                (5 * mem::size_of::<GLfloat>()) as *const _,
+            );
+        }
+
+        program.enable();
+        program.set_int("tex", 0);
+        program.set_int("shape_params", 1);
+
+        Batch {
+            vao,
+            vbo,
+            params: ParamsBuffer::new(),
+            program,
+            buffer: BatchBuffer::new(),
+
+            draw_calls: 0,
+        }
+    }
+
+    /// Draw calls issued since the last `reset_draw_calls`.
+    pub fn get_draw_calls(&self) -> usize {
+        return self.draw_calls;
+    }
+
+    pub fn reset_draw_calls(&mut self) {
+        self.draw_calls = 0;
+    }
+
+    pub fn get_program(&self) -> &ShaderProgram {
+        return &self.program;
+    }
+
+    pub fn get_transform(&self) -> &Transform {
+        return self.buffer.get_transform();
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.buffer.set_transform(transform);
+    }
+
+    pub fn push(
+        &mut self,
+        vertices: &[Vertex],
+        color: [f32; 4],
+        kind: ShapeKind,
+        texture: Option<&Texture>,
+    ) {
+        self.push_with_params(vertices, color, kind, texture, &ShapeParams::NONE);
+    }
+
+    pub fn push_with_params(
+        &mut self,
+        vertices: &[Vertex],
+        color: [f32; 4],
+        kind: ShapeKind,
+        texture: Option<&Texture>,
+        params: &ShapeParams,
+    ) {
+        let texture = texture.map(|t| t.id);
+
+        if !self.buffer.fits(vertices.len(), kind, texture) {
+            self.flush();
+        }
+
+        self.buffer.push(vertices, color, kind, texture, params);
+    }
+
+    /// Adds a quad given its corners in counter-clockwise order.
+    pub fn push_quad(
+        &mut self,
+        corners: &[Vertex; 4],
+        color: [f32; 4],
+        kind: ShapeKind,
+        texture: Option<&Texture>,
+    ) {
+        self.push_quad_with_params(corners, color, kind, texture, &ShapeParams::NONE);
+    }
+
+    pub fn push_quad_with_params(
+        &mut self,
+        corners: &[Vertex; 4],
+        color: [f32; 4],
+        kind: ShapeKind,
+        texture: Option<&Texture>,
+        params: &ShapeParams,
+    ) {
+        let vertices = [
+            corners[0], corners[1], corners[2], corners[2], corners[3], corners[0],
+        ];
+        self.push_with_params(&vertices, color, kind, texture, params);
+    }
+
+    pub fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        self.vao.enable();
+        self.vbo.enable();
+        self.program.enable();
+
+        if let Some(texture) = self.buffer.get_texture() {
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+            }
+        }
+
+        self.vbo.update(self.buffer.as_slice());
+        self.params.update(self.buffer.get_params());
+
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, self.buffer.vertex_count() as i32);
+        }
+
+        self.draw_calls += 1;
+        self.buffer.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> [Vertex; 3] {
+        return [
+            Vertex::new(0.0, 0.0, 0.0, 0.0),
+            Vertex::new(1.0, 0.0, 1.0, 0.0),
+            Vertex::new(1.0, 1.0, 1.0, 1.0),
+        ];
+    }
+
+    #[test]
+    fn untextured_primitives_share_any_batch() {
+        let mut buffer = BatchBuffer::new();
+        buffer.push(
+            &triangle(),
+            [1.0; 4],
+            ShapeKind::Texture,
+            Some(7),
+            &ShapeParams::NONE,
+        );
+        assert!(buffer.fits(3, ShapeKind::Solid, None));
+        assert!(buffer.fits(3, ShapeKind::Texture, Some(7)));
+        assert!(!buffer.fits(3, ShapeKind::Texture, Some(8)));
+
+        buffer.push(
+            &triangle(),
+            [1.0; 4],
+            ShapeKind::Solid,
+            None,
+            &ShapeParams::NONE,
+        );
+        assert_eq!(buffer.get_texture(), Some(7));
+        assert_eq!(buffer.vertex_count(), 6);
+    }
+
+    #[test]
+    fn full_buffers_do_not_fit() {
+        let mut buffer = BatchBuffer::new();
+        assert!(buffer.fits(MAX_VERTICES, ShapeKind::Solid, None));
+        assert!(!buffer.fits(MAX_VERTICES + 1, ShapeKind::Solid, None));
+
+        buffer.push(
+            &triangle(),
+            [1.0; 4],
+            ShapeKind::Solid,
+            None,
+            &ShapeParams::NONE,
+        );
+        assert!(!buffer.fits(MAX_VERTICES - 2, ShapeKind::Solid, None));
+    }
+
+    #[test]
+    fn full_params_do_not_fit() {
+        let mut buffer = BatchBuffer::new();
+        for _ in 0..MAX_PARAM_TEXELS / 5 {
+            buffer.push(
+                &triangle()[..1],
+                [1.0; 4],
+                ShapeKind::Circle,
+                None,
+                &ShapeParams::NONE,
+            );
+        }
+        assert!(buffer.fits(1, ShapeKind::Solid, None));
+        assert!(!buffer.fits(1, ShapeKind::Circle, None));
+    }
+
+    #[test]
+    fn positions_are_transformed_as_they_are_pushed() {
+        let mut buffer = BatchBuffer::new();
+        buffer.set_transform(Transform::translation(10.0, 20.0).scale(2.0, 3.0));
+        buffer.push(
+            &triangle()[2..],
+            [1.0; 4],
+            ShapeKind::Solid,
+            None,
+            &ShapeParams::NONE,
+        );
+        assert_eq!(buffer.as_slice()[0].position, [12.0, 23.0]);
+        assert_eq!(buffer.as_slice()[0].uv, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn gradients_pack_their_mode_into_extra() {
+        let params: ShapeParams = RectParams {
+            stroke_color: [0.0; 4],
+            radii: [1.0, 2.0, 3.0, 4.0],
+            half_width: 10.0,
+            half_height: 5.0,
+            stroke_width: 2.0,
+            gradient: Gradient::Radial {
+                center: [1.0, -1.0],
+                radius: 4.0,
+                end_color: [1.0; 4],
+            },
+        }
+        .into();
+        assert_eq!(params.extra, [10.0, 5.0, 2.0, 2.0]);
+        assert_eq!(params.gradient, [1.0, -1.0, 4.0, 0.0]);
+        assert_eq!(params.gradient_color, [1.0; 4]);
+
+        let params: ShapeParams = EllipseParams {
+            stroke_color: [0.0; 4],
+            radius_x: 1.0,
+            radius_y: 1.0,
+            stroke_width: 0.0,
+            gradient: Gradient::None,
+        }
+        .into();
+        assert_eq!(params.extra[3], 0.0);
+    }
+
+    #[test]
+    fn shapes_store_only_the_params_their_kind_reads() {
+        let mut buffer = BatchBuffer::new();
+        let params = ShapeParams {
+            color: [0.5, 0.6, 0.7, 0.8],
+            values: [2.0, 3.0, 4.0, 5.0],
+            extra: [6.0; 4],
+            gradient_color: [7.0; 4],
+            gradient: [8.0; 4],
+        };
+        buffer.push(
+            &triangle()[..1],
+            [0.1, 0.2, 0.3, 0.4],
+            ShapeKind::Circle,
+            None,
+            &params,
+        );
+        buffer.push(&triangle(), [1.0; 4], ShapeKind::SdfGlyph, None, &params);
+        buffer.push(&triangle(), [1.0; 4], ShapeKind::Solid, None, &params);
+
+        assert_eq!(mem::size_of::<PackedVertex>(), 24);
+        assert_eq!(
+            buffer.as_slice()[0],
+            PackedVertex {
+                position: [0.0, 0.0],
+                uv: [0.0, 0.0],
+                color: [26, 51, 77, 102],
+                shape: 1,
+            }
+        );
+        assert!(buffer.as_slice()[1..4]
+            .iter()
+            .all(|v| v.shape == ShapeKind::SdfGlyph as u32 | 5 << 4));
+        assert!(buffer.as_slice()[4..]
+            .iter()
+            .all(|v| v.shape == ShapeKind::Solid as u32 | 7 << 4));
+        assert_eq!(
+            buffer.get_params(),
+            &[
+                [0.5, 0.6, 0.7, 0.8],
+                [2.0, 3.0, 4.0, 5.0],
+                [6.0; 4],
+                [7.0; 4],
+                [8.0; 4],
+                [0.5, 0.6, 0.7, 0.8],
+                [2.0, 3.0, 4.0, 5.0]
+            ][..]
+        );
+
+        buffer.clear();
+        assert!(buffer.is_empty());
+        assert!(buffer.get_params().is_empty());
+        assert_eq!(buffer.get_texture(), None);
+    }
+}
@@ -0,0 +1,96 @@
+use super::transform::Transform;
+use crate::primitives::Point;
+
+/// Looks at a world larger than the view, for scrolling and zooming scenes.
+///
+/// `position` is the world point shown at the center of the view. A `zoom` above 1 magnifies the world, and a
+/// positive `rotation` (radians) turns the camera counter-clockwise, so the world appears to turn clockwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2D {
+    pub position: Point<f32>,
+    pub zoom: f32,
+    pub rotation: f32,
+}
+
+impl Camera2D {
+    pub fn new(position: Point<f32>) -> Self {
+        return Camera2D {
+            position,
+            zoom: 1.0,
+            rotation: 0.0,
+        };
+    }
+
+    pub fn zoom(mut self, zoom: f32) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
+    pub fn rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Maps world coordinates to the coordinates of a view of the given logical size.
+    pub fn get_transform(&self, view_width: f32, view_height: f32) -> Transform {
+        return Transform::translation(view_width / 2.0, view_height / 2.0)
+            .rotate(-self.rotation)
+            .scale(self.zoom, self.zoom)
+            .translate(-self.position.x, -self.position.y);
+    }
+
+    /// Where a world point appears in a view of the given size.
+    pub fn world_to_view(
+        &self,
+        point: &Point<f32>,
+        view_width: f32,
+        view_height: f32,
+    ) -> Point<f32> {
+        return self
+            .get_transform(view_width, view_height)
+            .transform_point(point);
+    }
+
+    /// The world point under a point of a view of the given size, like the mouse cursor after
+    /// `Graphics::window_to_view`. A zoom of 0 sees nothing, and maps everything to `position`.
+    pub fn view_to_world(
+        &self,
+        point: &Point<f32>,
+        view_width: f32,
+        view_height: f32,
+    ) -> Point<f32> {
+        return match self.get_transform(view_width, view_height).inverse() {
+            Some(inverse) => inverse.transform_point(point),
+            None => self.position,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_is_at_the_center_of_the_view() {
+        let camera = Camera2D::new(Point::new(100.0, 50.0))
+            .zoom(2.0)
+            .rotation(1.0);
+
+        let center = camera.world_to_view(&camera.position, 160.0, 120.0);
+        assert!((center.x - 80.0).abs() < 0.0001 && (center.y - 60.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn view_to_world_undoes_world_to_view() {
+        let camera = Camera2D::new(Point::new(-20.0, 30.0))
+            .zoom(4.0)
+            .rotation(std::f32::consts::PI / 2.0);
+
+        // Ten units above the camera, four times as far and turned a quarter clockwise
+        let view = camera.world_to_view(&Point::new(-20.0, 40.0), 160.0, 120.0);
+        assert!((view.x - 120.0).abs() < 0.0001 && (view.y - 60.0).abs() < 0.0001);
+
+        let world = camera.view_to_world(&view, 160.0, 120.0);
+        assert!((world.x + 20.0).abs() < 0.0001 && (world.y - 40.0).abs() < 0.0001);
+    }
+}
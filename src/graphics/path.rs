@@ -0,0 +1,706 @@
+//! Vector paths, flattened into polylines and tessellated into triangles for the batch.
+
+use crate::primitives::Point;
+use std::f32::consts::PI;
+
+/// How far flattened curves may stray from the real ones, in physical pixels.
+pub const TOLERANCE: f32 = 0.25;
+
+// Upper bound on the segments one curve or arc is split into
+const MAX_SEGMENTS: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PathCommand {
+    MoveTo(Point<f32>),
+    LineTo(Point<f32>),
+    QuadTo(Point<f32>, Point<f32>),
+    CubicTo(Point<f32>, Point<f32>, Point<f32>),
+    Arc {
+        center: Point<f32>,
+        radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+    },
+    Close,
+}
+
+/// A shape made of lines, curves and arcs, built up one command at a time. Drawn with
+/// `SpriteRenderer::fill_path` and `SpriteRenderer::stroke_path`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    commands: Vec<PathCommand>,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        return Path {
+            commands: Vec::new(),
+        };
+    }
+
+    /// Starts a new subpath at (`x`, `y`).
+    pub fn move_to(mut self, x: f32, y: f32) -> Self {
+        self.commands.push(PathCommand::MoveTo(Point::new(x, y)));
+        self
+    }
+
+    pub fn line_to(mut self, x: f32, y: f32) -> Self {
+        self.commands.push(PathCommand::LineTo(Point::new(x, y)));
+        self
+    }
+
+    /// A quadratic Bézier curve to (`x`, `y`) pulled towards the control point (`cx`, `cy`).
+    pub fn quad_to(mut self, cx: f32, cy: f32, x: f32, y: f32) -> Self {
+        self.commands
+            .push(PathCommand::QuadTo(Point::new(cx, cy), Point::new(x, y)));
+        self
+    }
+
+    /// A cubic Bézier curve to (`x`, `y`) with two control points.
+    pub fn cubic_to(mut self, c1x: f32, c1y: f32, c2x: f32, c2y: f32, x: f32, y: f32) -> Self {
+        self.commands.push(PathCommand::CubicTo(
+            Point::new(c1x, c1y),
+            Point::new(c2x, c2y),
+            Point::new(x, y),
+        ));
+        self
+    }
+
+    /// A circular arc around (`x`, `y`) from `start_angle` to `end_angle`, in radians counter-clockwise
+    /// from the positive x axis. Goes clockwise if `end_angle` is smaller. A line connects the
+    /// current point to the start of the arc.
+    pub fn arc(mut self, x: f32, y: f32, radius: f32, start_angle: f32, end_angle: f32) -> Self {
+        self.commands.push(PathCommand::Arc {
+            center: Point::new(x, y),
+            radius,
+            start_angle,
+            end_angle,
+        });
+        self
+    }
+
+    /// Connects the current subpath back to its start.
+    pub fn close(mut self) -> Self {
+        self.commands.push(PathCommand::Close);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.commands.is_empty();
+    }
+
+    /// Replaces curves and arcs by line segments no further than `tolerance` from them.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Polyline> {
+        let mut polylines = Vec::new();
+        let mut current: Option<Polyline> = None;
+        // Where a subpath continued after `close` starts
+        let mut last_start: Option<Point<f32>> = None;
+
+        for command in &self.commands {
+            match *command {
+                PathCommand::MoveTo(point) => {
+                    polylines.extend(current.take());
+                    current = Some(Polyline::new(point));
+                }
+                PathCommand::LineTo(point) => {
+                    start_polyline(&mut current, last_start.unwrap_or(point)).push(point);
+                }
+                PathCommand::QuadTo(control, end) => {
+                    let polyline = start_polyline(&mut current, last_start.unwrap_or(control));
+                    let start = polyline.last();
+                    let dd = length(add(sub(start, scale(control, 2.0)), end));
+                    let segments = segment_count((dd / (4.0 * tolerance)).sqrt());
+                    for i in 1..=segments {
+                        let t = i as f32 / segments as f32;
+                        let mt = 1.0 - t;
+                        polyline.push(add(
+                            add(scale(start, mt * mt), scale(control, 2.0 * mt * t)),
+                            scale(end, t * t),
+                        ));
+                    }
+                }
+                PathCommand::CubicTo(control1, control2, end) => {
+                    let polyline = start_polyline(&mut current, last_start.unwrap_or(control1));
+                    let start = polyline.last();
+                    let dd1 = length(add(sub(start, scale(control1, 2.0)), control2));
+                    let dd2 = length(add(sub(control1, scale(control2, 2.0)), end));
+                    let dd = dd1.max(dd2);
+                    let segments = segment_count((3.0 * dd / (4.0 * tolerance)).sqrt());
+                    for i in 1..=segments {
+                        let t = i as f32 / segments as f32;
+                        let mt = 1.0 - t;
+                        polyline.push(add(
+                            add(
+                                scale(start, mt * mt * mt),
+                                scale(control1, 3.0 * mt * mt * t),
+                            ),
+                            add(scale(control2, 3.0 * mt * t * t), scale(end, t * t * t)),
+                        ));
+                    }
+                }
+                PathCommand::Arc {
+                    center,
+                    radius,
+                    start_angle,
+                    end_angle,
+                } => {
+                    let start = add(center, polar(radius, start_angle));
+                    let polyline = start_polyline(&mut current, start);
+                    polyline.push(start);
+
+                    let sweep = end_angle - start_angle;
+                    let segments = arc_segments(radius, sweep, tolerance);
+                    for i in 1..=segments {
+                        let angle = start_angle + sweep * i as f32 / segments as f32;
+                        polyline.push(add(center, polar(radius, angle)));
+                    }
+                }
+                PathCommand::Close => {
+                    if let Some(mut polyline) = current.take() {
+                        polyline.closed = true;
+                        last_start = Some(polyline.points[0]);
+                        polylines.push(polyline);
+                    }
+                    continue;
+                }
+            }
+
+            last_start = None;
+        }
+
+        polylines.extend(current);
+        return polylines;
+    }
+}
+
+/// A run of connected points, what a subpath flattens into.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polyline {
+    pub points: Vec<Point<f32>>,
+    /// Whether the last point connects back to the first.
+    pub closed: bool,
+}
+
+impl Polyline {
+    fn new(start: Point<f32>) -> Self {
+        return Polyline {
+            points: vec![start],
+            closed: false,
+        };
+    }
+
+    fn last(&self) -> Point<f32> {
+        return self.points[self.points.len() - 1];
+    }
+
+    // Skips points on top of the previous one, they have no direction to join or cap
+    fn push(&mut self, point: Point<f32>) {
+        if length(sub(point, self.last())) > 1e-5 {
+            self.points.push(point);
+        }
+    }
+
+    // The points without a closing duplicate of the first
+    fn open_points(&self) -> &[Point<f32>] {
+        let points = &self.points[..];
+        if points.len() > 1 && length(sub(points[0], points[points.len() - 1])) <= 1e-5 {
+            return &points[..points.len() - 1];
+        }
+        return points;
+    }
+}
+
+fn start_polyline(current: &mut Option<Polyline>, start: Point<f32>) -> &mut Polyline {
+    return current.get_or_insert_with(|| Polyline::new(start));
+}
+
+/// How the corners between two segments of a stroke are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineJoin {
+    /// The outer edges are extended until they meet, up to the stroke's miter limit.
+    Miter,
+    Round,
+    /// The corner is cut off straight.
+    Bevel,
+}
+
+/// How the ends of an open subpath are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineCap {
+    /// The stroke stops at the end point.
+    Butt,
+    Round,
+    /// The stroke goes on for half its width past the end point.
+    Square,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeStyle {
+    /// In drawing units, centered on the path.
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// The longest a miter may get, as a multiple of the width, before it is beveled instead.
+    pub miter_limit: f32,
+}
+
+impl StrokeStyle {
+    /// Miter joins limited to 4 times the width, and butt caps.
+    pub fn new(width: f32) -> Self {
+        return StrokeStyle {
+            width,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+        };
+    }
+
+    pub fn join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn miter_limit(mut self, miter_limit: f32) -> Self {
+        self.miter_limit = miter_limit;
+        self
+    }
+}
+
+pub type Triangle = [Point<f32>; 3];
+
+/// Triangulates the inside of every polyline, closing open ones. Concave polylines are fine, but
+/// each one is filled on its own, so overlapping polylines do not cut holes into each other.
+pub fn fill(polylines: &[Polyline]) -> Vec<Triangle> {
+    let mut triangles = Vec::new();
+    for polyline in polylines {
+        triangulate(polyline.open_points(), &mut triangles);
+    }
+    return triangles;
+}
+
+// Ear clipping, which handles any simple polygon
+fn triangulate(points: &[Point<f32>], triangles: &mut Vec<Triangle>) {
+    if points.len() < 3 {
+        return;
+    }
+
+    // Work counter-clockwise, so ears are the corners turning left
+    let mut indices: Vec<usize> = (0..points.len()).collect();
+    if signed_area(points) < 0.0 {
+        indices.reverse();
+    }
+
+    let mut i = 0;
+    let mut attempts = 0;
+    while indices.len() > 3 {
+        let count = indices.len();
+        i %= count;
+        let a = points[indices[(i + count - 1) % count]];
+        let b = points[indices[i]];
+        let c = points[indices[(i + 1) % count]];
+
+        let turn = cross(sub(b, a), sub(c, b));
+        if turn.abs() <= 1e-6 {
+            // A straight corner covers nothing
+            indices.remove(i);
+            attempts = 0;
+            continue;
+        }
+
+        let is_ear = turn > 0.0
+            && indices.iter().all(|&j| {
+                let p = points[j];
+                let is_corner = p == a || p == b || p == c;
+                return is_corner || !in_triangle(p, a, b, c);
+            });
+
+        // Self-intersecting polylines can run out of ears, clip whatever is next then
+        if is_ear || attempts >= count {
+            triangles.push([a, b, c]);
+            indices.remove(i);
+            attempts = 0;
+        } else {
+            i += 1;
+            attempts += 1;
+        }
+    }
+
+    triangles.push([points[indices[0]], points[indices[1]], points[indices[2]]]);
+}
+
+/// Triangulates a stroke along every polyline. `tolerance` is how far round joins and caps may
+/// stray from real circles.
+///
+/// Joins overlap the segments they connect, so translucent strokes come out darker at the corners.
+pub fn stroke(polylines: &[Polyline], style: &StrokeStyle, tolerance: f32) -> Vec<Triangle> {
+    let mut triangles = Vec::new();
+    let half_width = style.width / 2.0;
+    if half_width <= 0.0 {
+        return triangles;
+    }
+
+    for polyline in polylines {
+        let mut points = polyline.open_points().to_vec();
+        let closed = polyline.closed && points.len() > 2;
+        if points.len() < 2 {
+            continue;
+        }
+
+        let last = points.len() - 1;
+        let start_direction = normalize(sub(points[1], points[0]));
+        let end_direction = normalize(sub(points[last], points[last - 1]));
+
+        if !closed && style.cap == LineCap::Square {
+            points[0] = sub(points[0], scale(start_direction, half_width));
+            points[last] = add(points[last], scale(end_direction, half_width));
+        }
+
+        let segment_count = if closed {
+            points.len()
+        } else {
+            points.len() - 1
+        };
+        for i in 0..segment_count {
+            let a = points[i];
+            let b = points[(i + 1) % points.len()];
+            let offset = scale(normal(normalize(sub(b, a))), half_width);
+            let corners = [
+                add(a, offset),
+                sub(a, offset),
+                sub(b, offset),
+                add(b, offset),
+            ];
+            triangles.push([corners[0], corners[1], corners[2]]);
+            triangles.push([corners[2], corners[3], corners[0]]);
+        }
+
+        let joins = if closed {
+            0..points.len()
+        } else {
+            1..points.len() - 1
+        };
+        for i in joins {
+            let previous = points[(i + points.len() - 1) % points.len()];
+            let point = points[i];
+            let next = points[(i + 1) % points.len()];
+            join(
+                point,
+                normalize(sub(point, previous)),
+                normalize(sub(next, point)),
+                half_width,
+                style,
+                tolerance,
+                &mut triangles,
+            );
+        }
+
+        if !closed && style.cap == LineCap::Round {
+            let start_normal = normal(start_direction);
+            fan(
+                points[0],
+                half_width,
+                start_normal.y.atan2(start_normal.x),
+                PI,
+                tolerance,
+                &mut triangles,
+            );
+            let end_normal = normal(end_direction);
+            fan(
+                points[last],
+                half_width,
+                end_normal.y.atan2(end_normal.x),
+                -PI,
+                tolerance,
+                &mut triangles,
+            );
+        }
+    }
+
+    return triangles;
+}
+
+// Fills the gap on the outside of the corner at `point`
+fn join(
+    point: Point<f32>,
+    incoming: Point<f32>,
+    outgoing: Point<f32>,
+    half_width: f32,
+    style: &StrokeStyle,
+    tolerance: f32,
+    triangles: &mut Vec<Triangle>,
+) {
+    let turn = cross(incoming, outgoing);
+    if turn.abs() <= 1e-6 && dot(incoming, outgoing) > 0.0 {
+        return;
+    }
+
+    // Turning left opens the gap on the right
+    let side = if turn > 0.0 { -1.0 } else { 1.0 };
+    let outer_in = scale(normal(incoming), side);
+    let outer_out = scale(normal(outgoing), side);
+    let from = add(point, scale(outer_in, half_width));
+    let to = add(point, scale(outer_out, half_width));
+
+    match style.join {
+        LineJoin::Round => {
+            let sweep = cross(outer_in, outer_out).atan2(dot(outer_in, outer_out));
+            fan(
+                point,
+                half_width,
+                outer_in.y.atan2(outer_in.x),
+                sweep,
+                tolerance,
+                triangles,
+            );
+        }
+        LineJoin::Miter => {
+            let bisector = normalize(add(outer_in, outer_out));
+            let cos_half_angle = dot(bisector, outer_in);
+            // Half the width times the miter ratio, measured from the path
+            if cos_half_angle > 1e-6 && 1.0 / cos_half_angle <= style.miter_limit {
+                let tip = add(point, scale(bisector, half_width / cos_half_angle));
+                triangles.push([point, from, tip]);
+                triangles.push([point, tip, to]);
+            } else {
+                triangles.push([point, from, to]);
+            }
+        }
+        LineJoin::Bevel => triangles.push([point, from, to]),
+    }
+}
+
+// A circular fan around `center`, `sweep` radians counter-clockwise from `start_angle`
+fn fan(
+    center: Point<f32>,
+    radius: f32,
+    start_angle: f32,
+    sweep: f32,
+    tolerance: f32,
+    triangles: &mut Vec<Triangle>,
+) {
+    let segments = arc_segments(radius, sweep, tolerance);
+    let mut previous = add(center, polar(radius, start_angle));
+    for i in 1..=segments {
+        let angle = start_angle + sweep * i as f32 / segments as f32;
+        let point = add(center, polar(radius, angle));
+        triangles.push([center, previous, point]);
+        previous = point;
+    }
+}
+
+// Segments keeping a chord of an arc within `tolerance` of it
+fn arc_segments(radius: f32, sweep: f32, tolerance: f32) -> usize {
+    if radius <= tolerance {
+        return segment_count(sweep.abs() / (PI / 2.0));
+    }
+    let step = 2.0 * (1.0 - tolerance / radius).acos();
+    return segment_count(sweep.abs() / step);
+}
+
+fn segment_count(segments: f32) -> usize {
+    if !segments.is_finite() {
+        return 1;
+    }
+    return (segments.ceil() as usize).clamp(1, MAX_SEGMENTS);
+}
+
+fn signed_area(points: &[Point<f32>]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    return area / 2.0;
+}
+
+// Includes the edges, so points touching an ear keep it from being clipped
+fn in_triangle(p: Point<f32>, a: Point<f32>, b: Point<f32>, c: Point<f32>) -> bool {
+    return cross(sub(b, a), sub(p, a)) >= 0.0
+        && cross(sub(c, b), sub(p, b)) >= 0.0
+        && cross(sub(a, c), sub(p, c)) >= 0.0;
+}
+
+fn add(a: Point<f32>, b: Point<f32>) -> Point<f32> {
+    return Point::new(a.x + b.x, a.y + b.y);
+}
+
+fn sub(a: Point<f32>, b: Point<f32>) -> Point<f32> {
+    return Point::new(a.x - b.x, a.y - b.y);
+}
+
+fn scale(a: Point<f32>, factor: f32) -> Point<f32> {
+    return Point::new(a.x * factor, a.y * factor);
+}
+
+fn dot(a: Point<f32>, b: Point<f32>) -> f32 {
+    return a.x * b.x + a.y * b.y;
+}
+
+fn cross(a: Point<f32>, b: Point<f32>) -> f32 {
+    return a.x * b.y - a.y * b.x;
+}
+
+fn length(a: Point<f32>) -> f32 {
+    return dot(a, a).sqrt();
+}
+
+fn normalize(a: Point<f32>) -> Point<f32> {
+    let length = length(a);
+    if length == 0.0 {
+        return a;
+    }
+    return scale(a, 1.0 / length);
+}
+
+// Rotated a quarter turn counter-clockwise, to the left of the direction
+fn normal(direction: Point<f32>) -> Point<f32> {
+    return Point::new(-direction.y, direction.x);
+}
+
+fn polar(radius: f32, angle: f32) -> Point<f32> {
+    return Point::new(radius * angle.cos(), radius * angle.sin());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(triangles: &[Triangle]) -> f32 {
+        return triangles
+            .iter()
+            .map(|[a, b, c]| cross(sub(*b, *a), sub(*c, *a)).abs() / 2.0)
+            .sum();
+    }
+
+    #[test]
+    fn lines_flatten_to_their_points() {
+        let polylines = Path::new()
+            .move_to(0.0, 0.0)
+            .line_to(10.0, 0.0)
+            .line_to(10.0, 0.0)
+            .line_to(10.0, 5.0)
+            .close()
+            .line_to(-5.0, 0.0)
+            .flatten(TOLERANCE);
+
+        assert_eq!(polylines.len(), 2);
+        assert_eq!(polylines[0].points.len(), 3);
+        assert!(polylines[0].closed);
+        // Drawing on after close starts from the closed subpath's start
+        assert_eq!(polylines[1].points[0], Point::new(0.0, 0.0));
+        assert!(!polylines[1].closed);
+    }
+
+    #[test]
+    fn curves_stay_within_the_tolerance() {
+        let polylines = Path::new()
+            .move_to(0.0, 0.0)
+            .quad_to(50.0, 100.0, 100.0, 0.0)
+            .cubic_to(120.0, -50.0, 180.0, -50.0, 200.0, 0.0)
+            .flatten(0.1);
+
+        let points = &polylines[0].points;
+        assert!(points.len() > 10);
+        assert_eq!(points[points.len() - 1], Point::new(200.0, 0.0));
+        // The top of the quadratic curve is at half the control point's height
+        let top = points.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+        assert!((top - 50.0).abs() < 0.1);
+
+        let arc = Path::new().arc(0.0, 0.0, 10.0, 0.0, PI).flatten(0.1);
+        let points = &arc[0].points;
+        assert!(points.iter().all(|p| (length(*p) - 10.0).abs() < 1e-3));
+        assert!(length(sub(points[points.len() - 1], Point::new(-10.0, 0.0))) < 1e-3);
+    }
+
+    #[test]
+    fn concave_polygons_fill_exactly() {
+        // An L shape, clockwise, with a straight corner along the bottom
+        let polylines = Path::new()
+            .move_to(0.0, 0.0)
+            .line_to(0.0, 20.0)
+            .line_to(10.0, 20.0)
+            .line_to(10.0, 10.0)
+            .line_to(20.0, 10.0)
+            .line_to(20.0, 0.0)
+            .line_to(10.0, 0.0)
+            .flatten(TOLERANCE);
+
+        let triangles = fill(&polylines);
+        assert_eq!(triangles.len(), 4);
+        assert!((area(&triangles) - 300.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn butt_and_square_caps() {
+        let line = Path::new()
+            .move_to(0.0, 0.0)
+            .line_to(10.0, 0.0)
+            .flatten(TOLERANCE);
+
+        let butt = stroke(&line, &StrokeStyle::new(2.0), TOLERANCE);
+        assert!((area(&butt) - 20.0).abs() < 1e-3);
+
+        let square = stroke(
+            &line,
+            &StrokeStyle::new(2.0).cap(LineCap::Square),
+            TOLERANCE,
+        );
+        assert!((area(&square) - 24.0).abs() < 1e-3);
+
+        let round = stroke(&line, &StrokeStyle::new(2.0).cap(LineCap::Round), 0.001);
+        assert!((area(&round) - (20.0 + PI)).abs() < 0.01);
+    }
+
+    #[test]
+    fn joins_fill_the_outside_of_corners() {
+        let corner = Path::new()
+            .move_to(0.0, 0.0)
+            .line_to(10.0, 0.0)
+            .line_to(10.0, 10.0)
+            .flatten(TOLERANCE);
+        let segments = 40.0;
+
+        // A right angle: the miter fills a 1x1 square, the bevel half of it
+        let miter = stroke(&corner, &StrokeStyle::new(2.0), TOLERANCE);
+        assert!((area(&miter) - segments - 1.0).abs() < 1e-3);
+        assert!(miter
+            .iter()
+            .flatten()
+            .any(|p| length(sub(*p, Point::new(11.0, -1.0))) < 1e-4));
+
+        let bevel = stroke(
+            &corner,
+            &StrokeStyle::new(2.0).join(LineJoin::Bevel),
+            TOLERANCE,
+        );
+        assert!((area(&bevel) - segments - 0.5).abs() < 1e-3);
+
+        let round = stroke(&corner, &StrokeStyle::new(2.0).join(LineJoin::Round), 0.001);
+        assert!((area(&round) - segments - PI / 4.0).abs() < 0.01);
+
+        // Past the limit miters turn into bevels
+        let limited = stroke(&corner, &StrokeStyle::new(2.0).miter_limit(1.2), TOLERANCE);
+        assert!((area(&limited) - area(&bevel)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn closed_strokes_join_at_the_start() {
+        let square = Path::new()
+            .move_to(0.0, 0.0)
+            .line_to(10.0, 0.0)
+            .line_to(10.0, 10.0)
+            .line_to(0.0, 10.0)
+            .close()
+            .flatten(TOLERANCE);
+
+        // A 12x12 square minus the 8x8 inside, with the 4 segments overlapping the corners
+        let triangles = stroke(&square, &StrokeStyle::new(2.0), TOLERANCE);
+        assert_eq!(triangles.len(), 4 * 2 + 4 * 2);
+        assert!((area(&triangles) - (4.0 * 20.0 + 4.0)).abs() < 1e-3);
+    }
+}
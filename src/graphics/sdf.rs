@@ -0,0 +1,575 @@
+//! Signed distance field glyphs, rasterized once per font and drawn sharp at any size.
+
+use super::{
+    atlas::AtlasTarget,
+    Color,
+};
+use crate::primitives::Point;
+use rusttype::{
+    Font,
+    GlyphId,
+    Rect,
+    Scale,
+};
+use std::collections::HashMap;
+
+/// The font size glyphs are rasterized at before being turned into distance fields.
+pub const SDF_SIZE: f32 = 48.0;
+
+/// How many pixels of distance are stored on either side of a glyph's edge, at `SDF_SIZE`.
+/// Also the widest outline or blur the field can express.
+pub const SPREAD: u32 = 8;
+
+/// Size the atlas starts out at.
+pub const INITIAL_SIZE: u32 = 256;
+
+/// Gap left between glyphs so linear filtering does not bleed between them.
+const PADDING: u32 = 1;
+
+/// How text is turned into pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode {
+    /// Glyphs are rasterized for every size they are drawn at. The sharpest result for text that
+    /// keeps its size.
+    Bitmap,
+    /// Glyphs are rasterized once into a distance field and scaled freely, with optional outlines and
+    /// drop shadows from `TextEffects`.
+    Sdf,
+}
+
+/// Outlines and shadows for text drawn in `TextMode::Sdf`. Widths are in drawing units and are
+/// limited to what the distance field holds, `SPREAD / SDF_SIZE` of the font size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextEffects {
+    pub outline_width: f32,
+    pub outline_color: Color,
+    /// Where the shadow is drawn relative to the text.
+    pub shadow_offset: Point<f32>,
+    /// How far the shadow's edge is blurred.
+    pub shadow_softness: f32,
+    pub shadow_color: Color,
+}
+
+impl TextEffects {
+    /// No outline and no shadow.
+    pub fn new() -> Self {
+        return TextEffects {
+            outline_width: 0.0,
+            outline_color: Color::from_rgba(0, 0, 0, 0),
+            shadow_offset: Point::new(0.0, 0.0),
+            shadow_softness: 0.0,
+            shadow_color: Color::from_rgba(0, 0, 0, 0),
+        };
+    }
+
+    pub fn outline(mut self, width: f32, color: Color) -> Self {
+        self.outline_width = width;
+        self.outline_color = color;
+        self
+    }
+
+    pub fn shadow(mut self, offset: Point<f32>, softness: f32, color: Color) -> Self {
+        self.shadow_offset = offset;
+        self.shadow_softness = softness;
+        self.shadow_color = color;
+        self
+    }
+
+    pub fn has_shadow(&self) -> bool {
+        return self.shadow_color.a > 0;
+    }
+}
+
+/// Converts a distance in drawing units, for text of `size`, into distance field units.
+pub fn to_field_units(distance: f32, size: f32) -> f32 {
+    let pixels = distance * SDF_SIZE / size;
+    return (pixels / (2 * SPREAD) as f32).clamp(0.0, 0.5);
+}
+
+/// How many times finer than the field glyphs are rasterized, so edges fall between field pixels.
+pub const OVERSAMPLE: u32 = 4;
+
+// Stands in for infinity in the distance transform without turning into NaN
+const FAR: f64 = 1e20;
+
+/// Turns `coverage`, one value from 0 to 1 per pixel rasterized at `OVERSAMPLE` times the field's
+/// resolution, into a distance field padded by `SPREAD` on every side. Returns the field with its
+/// width and height. Edges map to 128, the inside goes up to 255 and the outside down to 0.
+pub fn distance_field(coverage: &[f32], width: u32, height: u32) -> (Vec<u8>, u32, u32) {
+    let field_width = width.div_ceil(OVERSAMPLE) + 2 * SPREAD;
+    let field_height = height.div_ceil(OVERSAMPLE) + 2 * SPREAD;
+
+    let padding = (SPREAD * OVERSAMPLE) as usize;
+    let fine_width = (field_width * OVERSAMPLE) as usize;
+    let fine_height = (field_height * OVERSAMPLE) as usize;
+
+    let mut inside = vec![false; fine_width * fine_height];
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            inside[(y + padding) * fine_width + x + padding] =
+                coverage[y * width as usize + x] >= 0.5;
+        }
+    }
+
+    // Squared distances to the nearest pixel inside, and to the nearest one outside
+    let mut to_inside: Vec<_> = inside.iter().map(|&i| if i { 0.0 } else { FAR }).collect();
+    let mut to_outside: Vec<_> = inside.iter().map(|&i| if i { FAR } else { 0.0 }).collect();
+    distance_transform(&mut to_inside, fine_width, fine_height);
+    distance_transform(&mut to_outside, fine_width, fine_height);
+
+    let oversample = OVERSAMPLE as usize;
+    let mut field = Vec::with_capacity((field_width * field_height) as usize);
+    for field_y in 0..field_height as usize {
+        for field_x in 0..field_width as usize {
+            // Averaged over the fine pixels this one covers, whose edge lies halfway to their neighbour
+            let mut sum = 0.0;
+            for y in field_y * oversample..(field_y + 1) * oversample {
+                for x in field_x * oversample..(field_x + 1) * oversample {
+                    let i = y * fine_width + x;
+                    sum += if inside[i] {
+                        to_outside[i].sqrt() - 0.5
+                    } else {
+                        0.5 - to_inside[i].sqrt()
+                    };
+                }
+            }
+
+            let distance = sum / (oversample * oversample) as f64 / oversample as f64;
+            let value = 0.5 + distance / (2 * SPREAD) as f64;
+            field.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+    }
+
+    return (field, field_width, field_height);
+}
+
+// Replaces every value in the grid with the squared distance to the nearest zero, following
+// Felzenszwalb and Huttenlocher's "Distance Transforms of Sampled Functions"
+fn distance_transform(grid: &mut [f64], width: usize, height: usize) {
+    let size = width.max(height);
+    let mut line = vec![0.0; size];
+    let mut result = vec![0.0; size];
+    let mut parabolas = vec![0; size];
+    let mut boundaries = vec![0.0; size + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            line[y] = grid[y * width + x];
+        }
+        distance_transform_line(
+            &line[..height],
+            &mut result,
+            &mut parabolas,
+            &mut boundaries,
+        );
+        for y in 0..height {
+            grid[y * width + x] = result[y];
+        }
+    }
+
+    for y in 0..height {
+        line[..width].copy_from_slice(&grid[y * width..(y + 1) * width]);
+        distance_transform_line(&line[..width], &mut result, &mut parabolas, &mut boundaries);
+        grid[y * width..(y + 1) * width].copy_from_slice(&result[..width]);
+    }
+}
+
+// The lower envelope of the parabolas rooted at every sample of `f`
+fn distance_transform_line(
+    f: &[f64],
+    result: &mut [f64],
+    parabolas: &mut [usize],
+    boundaries: &mut [f64],
+) {
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = -FAR;
+    boundaries[1] = FAR;
+
+    for q in 1..f.len() {
+        let intersection = |v: usize| {
+            let (q_f, v_f) = (q as f64, v as f64);
+            return ((f[q] + q_f * q_f) - (f[v] + v_f * v_f)) / (2.0 * q_f - 2.0 * v_f);
+        };
+
+        // Never drops below the first parabola, boundaries[0] is further left than any intersection
+        let mut s = intersection(parabolas[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(parabolas[k]);
+        }
+
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = FAR;
+    }
+
+    k = 0;
+    for (q, value) in result.iter_mut().enumerate().take(f.len()) {
+        while boundaries[k + 1] < q as f64 {
+            k += 1;
+        }
+        let distance = q as f64 - parabolas[k] as f64;
+        *value = distance * distance + f[parabolas[k]];
+    }
+}
+
+/// A glyph in the atlas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SdfGlyph {
+    /// Where the field is in the atlas, in pixels.
+    pub rect: Rect<u32>,
+    /// The area the field covers around the glyph origin at `SDF_SIZE`, y pointing down.
+    pub bounds: Rect<f32>,
+}
+
+/// Distance fields of every glyph drawn since the last `clear`. Glyphs are never evicted one by one,
+/// the atlas doubles in size when it fills up instead.
+pub struct SdfAtlas {
+    width: u32,
+    height: u32,
+    max_size: u32,
+    pixels: Vec<u8>,
+
+    // The row glyphs are currently placed in
+    cursor_x: u32,
+    cursor_y: u32,
+    row_height: u32,
+
+    // None for glyphs without an outline, like spaces
+    glyphs: HashMap<(usize, GlyphId), Option<SdfGlyph>>,
+}
+
+impl SdfAtlas {
+    pub fn new(max_size: u32) -> Self {
+        let size = INITIAL_SIZE.min(max_size);
+        return SdfAtlas {
+            width: size,
+            height: size,
+            max_size,
+            pixels: vec![0; (size * size) as usize],
+
+            cursor_x: 0,
+            cursor_y: 0,
+            row_height: 0,
+
+            glyphs: HashMap::new(),
+        };
+    }
+
+    pub fn get_dimensions(&self) -> (u32, u32) {
+        return (self.width, self.height);
+    }
+
+    pub fn set_max_size(&mut self, max_size: u32) {
+        self.max_size = max_size;
+    }
+
+    /// The uvs of `glyph` in the atlas as it is now.
+    pub fn uv_rect(&self, glyph: &SdfGlyph) -> Rect<f32> {
+        let width = self.width as f32;
+        let height = self.height as f32;
+        return Rect {
+            min: rusttype::point(
+                glyph.rect.min.x as f32 / width,
+                glyph.rect.min.y as f32 / height,
+            ),
+            max: rusttype::point(
+                glyph.rect.max.x as f32 / width,
+                glyph.rect.max.y as f32 / height,
+            ),
+        };
+    }
+
+    /// Forgets every glyph, they are rasterized again when next drawn. The atlas keeps its size and is
+    /// filled again from the top left.
+    pub fn clear(&mut self, target: &mut dyn AtlasTarget) {
+        // Batched glyphs point at space that is about to be reused
+        target.before_change();
+
+        self.glyphs.clear();
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.row_height = 0;
+
+        // Stale fields would bleed into the padding around new glyphs
+        self.pixels.iter_mut().for_each(|pixel| *pixel = 0);
+        target.upload(
+            Rect {
+                min: rusttype::point(0, 0),
+                max: rusttype::point(self.width, self.height),
+            },
+            &self.pixels,
+        );
+    }
+
+    /// Finds glyph `id` of `font`, rasterizing it into the atlas the first time. Returns None for
+    /// glyphs without an outline and when the atlas is full at its maximum size.
+    pub fn get(
+        &mut self,
+        font_id: usize,
+        font: &Font<'static>,
+        id: GlyphId,
+        target: &mut dyn AtlasTarget,
+    ) -> Option<SdfGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(font_id, id)) {
+            return *glyph;
+        }
+
+        let glyph = font
+            .glyph(id)
+            .scaled(Scale::uniform(SDF_SIZE * OVERSAMPLE as f32))
+            .positioned(rusttype::point(0.0, 0.0));
+
+        let bounding_box = match glyph.pixel_bounding_box() {
+            Some(bounding_box) => bounding_box,
+            None => {
+                self.glyphs.insert((font_id, id), None);
+                return None;
+            }
+        };
+
+        let width = bounding_box.width() as u32;
+        let height = bounding_box.height() as u32;
+        let mut coverage = vec![0.0; (width * height) as usize];
+        glyph.draw(|x, y, value| coverage[(y * width + x) as usize] = value);
+
+        let (field, field_width, field_height) = distance_field(&coverage, width, height);
+
+        // Not cached, so it is tried again if the maximum size is raised
+        let (x, y) = self.allocate(field_width, field_height, target)?;
+
+        for row in 0..field_height {
+            let start = ((y + row) * self.width + x) as usize;
+            let source = (row * field_width) as usize;
+            self.pixels[start..start + field_width as usize]
+                .copy_from_slice(&field[source..source + field_width as usize]);
+        }
+
+        let rect = Rect {
+            min: rusttype::point(x, y),
+            max: rusttype::point(x + field_width, y + field_height),
+        };
+        target.upload(rect, &field);
+
+        let min_x = bounding_box.min.x as f32 / OVERSAMPLE as f32 - SPREAD as f32;
+        let min_y = bounding_box.min.y as f32 / OVERSAMPLE as f32 - SPREAD as f32;
+        let sdf_glyph = SdfGlyph {
+            rect,
+            bounds: Rect {
+                min: rusttype::point(min_x, min_y),
+                max: rusttype::point(min_x + field_width as f32, min_y + field_height as f32),
+            },
+        };
+        self.glyphs.insert((font_id, id), Some(sdf_glyph));
+
+        return Some(sdf_glyph);
+    }
+
+    // Finds room for a width by height field, growing the atlas if needed
+    fn allocate(
+        &mut self,
+        width: u32,
+        height: u32,
+        target: &mut dyn AtlasTarget,
+    ) -> Option<(u32, u32)> {
+        loop {
+            if self.cursor_x + width > self.width {
+                self.cursor_x = 0;
+                self.cursor_y += self.row_height + PADDING;
+                self.row_height = 0;
+            }
+
+            if self.cursor_x + width <= self.width && self.cursor_y + height <= self.height {
+                let position = (self.cursor_x, self.cursor_y);
+                self.cursor_x += width + PADDING;
+                self.row_height = self.row_height.max(height);
+                return Some(position);
+            }
+
+            if self.width >= self.max_size && self.height >= self.max_size {
+                return None;
+            }
+
+            self.grow(target);
+        }
+    }
+
+    // Doubles the atlas, keeping every glyph where it is
+    fn grow(&mut self, target: &mut dyn AtlasTarget) {
+        // Batched glyphs have their uvs relative to the old size
+        target.before_change();
+
+        let width = (self.width * 2).min(self.max_size);
+        let height = (self.height * 2).min(self.max_size);
+
+        let mut pixels = vec![0; (width * height) as usize];
+        for row in 0..self.height {
+            let source = (row * self.width) as usize;
+            let start = (row * width) as usize;
+            pixels[start..start + self.width as usize]
+                .copy_from_slice(&self.pixels[source..source + self.width as usize]);
+        }
+
+        // The current row simply continues into the new space
+        self.width = width;
+        self.height = height;
+        self.pixels = pixels;
+
+        target.resize(width, height);
+        target.upload(
+            Rect {
+                min: rusttype::point(0, 0),
+                max: rusttype::point(width, height),
+            },
+            &self.pixels,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeTarget {
+        changes: usize,
+        resizes: Vec<(u32, u32)>,
+        uploads: usize,
+    }
+
+    impl AtlasTarget for FakeTarget {
+        fn before_change(&mut self) {
+            self.changes += 1;
+        }
+
+        fn resize(&mut self, width: u32, height: u32) {
+            self.resizes.push((width, height));
+        }
+
+        fn upload(&mut self, _rect: Rect<u32>, _data: &[u8]) {
+            self.uploads += 1;
+        }
+    }
+
+    fn font() -> Font<'static> {
+        return Font::from_bytes(&include_bytes!("../GoudyStMTT.ttf")[..]).unwrap();
+    }
+
+    #[test]
+    fn fields_are_signed_around_the_edge() {
+        // A 4x4 square in the field
+        let size = 4 * OVERSAMPLE;
+        let (field, width, height) = distance_field(&vec![1.0; (size * size) as usize], size, size);
+        assert_eq!((width, height), (4 + 2 * SPREAD, 4 + 2 * SPREAD));
+        let width = width as usize;
+
+        let at = |x: usize, y: usize| field[y * width + x];
+        let center = SPREAD as usize + 2;
+        assert!(at(center, center) > 128);
+        assert_eq!(at(0, 0), 0);
+        // Just inside and just outside the left edge
+        assert!(at(SPREAD as usize, center) > 128);
+        assert!(at(SPREAD as usize - 1, center) < 128);
+    }
+
+    #[test]
+    fn glyphs_are_rasterized_once() {
+        let font = font();
+        let mut atlas = SdfAtlas::new(4096);
+        let mut target = FakeTarget::default();
+
+        let id = font.glyph('A').id();
+        let glyph = atlas.get(0, &font, id, &mut target).unwrap();
+        assert_eq!(target.uploads, 1);
+        assert_eq!(
+            glyph.rect.width(),
+            (glyph.bounds.width()) as u32,
+            "the field covers the glyph and its spread"
+        );
+
+        assert_eq!(atlas.get(0, &font, id, &mut target), Some(glyph));
+        assert_eq!(target.uploads, 1);
+
+        // The same glyph of another font is separate
+        atlas.get(1, &font, id, &mut target).unwrap();
+        assert_eq!(target.uploads, 2);
+
+        assert_eq!(atlas.get(0, &font, font.glyph(' ').id(), &mut target), None);
+        assert_eq!(target.changes, 0);
+    }
+
+    #[test]
+    fn cleared_glyphs_are_rasterized_again_in_the_same_space() {
+        let font = font();
+        let mut atlas = SdfAtlas::new(4096);
+        let mut target = FakeTarget::default();
+
+        let id = font.glyph('A').id();
+        let old = atlas.get(0, &font, id, &mut target).unwrap();
+        atlas.get(1, &font, id, &mut target).unwrap();
+        assert_eq!(target.uploads, 2);
+
+        atlas.clear(&mut target);
+        assert_eq!(target.changes, 1);
+        // The whole atlas is uploaded blank
+        assert_eq!(target.uploads, 3);
+
+        let new = atlas.get(0, &font, id, &mut target).unwrap();
+        assert_eq!(target.uploads, 4);
+        assert_eq!(new.rect, old.rect);
+    }
+
+    #[test]
+    fn repeated_clears_do_not_grow_the_atlas() {
+        let font = font();
+        let mut atlas = SdfAtlas::new(4096);
+        let mut target = FakeTarget::default();
+        let initial = atlas.get_dimensions();
+
+        for _ in 0..20 {
+            for c in "Reload".chars() {
+                atlas
+                    .get(0, &font, font.glyph(c).id(), &mut target)
+                    .unwrap();
+            }
+            atlas.clear(&mut target);
+        }
+
+        assert_eq!(atlas.get_dimensions(), initial);
+        assert!(target.resizes.is_empty());
+    }
+
+    #[test]
+    fn full_atlases_grow_up_to_the_maximum() {
+        let font = font();
+        let mut atlas = SdfAtlas::new(512);
+        let mut target = FakeTarget::default();
+
+        let glyphs: Vec<_> = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789"
+            .chars()
+            .filter_map(|c| atlas.get(0, &font, font.glyph(c).id(), &mut target))
+            .collect();
+        assert_eq!(glyphs.len(), 62);
+        assert_eq!(atlas.get_dimensions(), (512, 512));
+        assert_eq!(target.resizes, vec![(512, 512)]);
+        assert_eq!(target.changes, 1);
+
+        // Placed glyphs never overlap
+        for (i, a) in glyphs.iter().enumerate() {
+            for b in &glyphs[i + 1..] {
+                let apart = a.rect.max.x <= b.rect.min.x
+                    || b.rect.max.x <= a.rect.min.x
+                    || a.rect.max.y <= b.rect.min.y
+                    || b.rect.max.y <= a.rect.min.y;
+                assert!(apart, "{:?} overlaps {:?}", a.rect, b.rect);
+            }
+        }
+
+        let mut small = SdfAtlas::new(32);
+        assert!(small
+            .get(0, &font, font.glyph('W').id(), &mut target)
+            .is_none());
+    }
+}
@@ -0,0 +1,597 @@
+//! Text layout: line breaking, alignment and measurement on top of `rusttype`.
+//!
+//! Layouts are computed in drawing units with y pointing up, relative to the left end of the first
+//! line's baseline, which is the point passed to `SpriteRenderer::draw_text`.
+
+use super::{
+    Color,
+    FontId,
+};
+use crate::primitives::Rect;
+use rusttype::{
+    Font,
+    GlyphId,
+    Scale,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// How text is broken into lines and placed.
+#[derive(Debug, Clone)]
+pub struct LayoutOptions {
+    /// Wrap lines longer than this. Words are kept whole unless a single word does not fit.
+    pub max_width: Option<f32>,
+    /// Lines are aligned within `max_width` if set, otherwise within the widest line.
+    pub align: TextAlign,
+    /// Multiplier for the distance between baselines. 1.0 uses the font's own line height.
+    pub line_spacing: f32,
+}
+
+impl LayoutOptions {
+    pub fn new() -> Self {
+        return LayoutOptions {
+            max_width: None,
+            align: TextAlign::Left,
+            line_spacing: 1.0,
+        };
+    }
+}
+
+/// Vertical metrics of a font at a given size, in drawing units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    /// Distance from the baseline to the top of the tallest glyphs.
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of the lowest glyphs. Negative, as it lies below.
+    pub descent: f32,
+    /// Extra space the font asks for between lines.
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    pub fn new(font: &Font, size: f32) -> Self {
+        let v_metrics = font.v_metrics(Scale::uniform(size));
+        return LineMetrics {
+            ascent: v_metrics.ascent,
+            descent: v_metrics.descent,
+            line_gap: v_metrics.line_gap,
+        };
+    }
+
+    /// The distance between two baselines.
+    pub fn get_line_height(&self) -> f32 {
+        return self.ascent - self.descent + self.line_gap;
+    }
+
+    // The metrics of a line holding text with both self and other
+    fn max(self, other: LineMetrics) -> LineMetrics {
+        return LineMetrics {
+            ascent: self.ascent.max(other.ascent),
+            descent: self.descent.min(other.descent),
+            line_gap: self.line_gap.max(other.line_gap),
+        };
+    }
+}
+
+/// A run of text sharing one style, part of a `RichText`.
+#[derive(Debug, Clone)]
+pub struct TextSpan {
+    pub text: String,
+    pub font: FontId,
+    pub size: f32,
+    pub color: Color,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl TextSpan {
+    pub fn new(text: &str, font: FontId, size: f32, color: Color) -> Self {
+        return TextSpan {
+            text: text.to_string(),
+            font,
+            size,
+            color,
+            underline: false,
+            strikethrough: false,
+        };
+    }
+
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    pub fn strikethrough(mut self) -> Self {
+        self.strikethrough = true;
+        self
+    }
+}
+
+/// A paragraph made of differently styled spans, laid out and drawn as one.
+#[derive(Debug, Clone)]
+pub struct RichText {
+    pub spans: Vec<TextSpan>,
+}
+
+impl RichText {
+    pub fn new() -> Self {
+        return RichText { spans: Vec::new() };
+    }
+
+    /// Appends a span, for building text in one expression.
+    pub fn with(mut self, span: TextSpan) -> Self {
+        self.spans.push(span);
+        self
+    }
+
+    pub fn push(&mut self, span: TextSpan) {
+        self.spans.push(span);
+    }
+}
+
+/// A glyph placed on its baseline.
+#[derive(Debug, Clone, Copy)]
+pub struct PlacedGlyph {
+    pub id: GlyphId,
+    pub font: FontId,
+    pub size: f32,
+    /// Index of the span the glyph came from.
+    pub span: usize,
+    pub x: f32,
+    pub y: f32,
+    pub advance: f32,
+}
+
+/// One line of a layout.
+#[derive(Debug, Clone, Copy)]
+pub struct LineLayout {
+    /// Where the line starts after alignment.
+    pub x: f32,
+    /// Width without trailing whitespace.
+    pub width: f32,
+    pub baseline: f32,
+    /// The largest metrics of all text on the line.
+    pub metrics: LineMetrics,
+}
+
+/// An underline or strikethrough, drawn in the color of its span.
+#[derive(Debug)]
+pub struct Decoration {
+    pub span: usize,
+    pub rect: Rect<f32>,
+}
+
+/// Text broken into lines and ready to draw with `SpriteRenderer::draw_layout`.
+pub struct TextLayout {
+    pub glyphs: Vec<PlacedGlyph>,
+    pub lines: Vec<LineLayout>,
+    pub decorations: Vec<Decoration>,
+}
+
+impl TextLayout {
+    /// Lays out `spans` as one paragraph. `fonts` holds the registered fonts, indexed by `FontId`.
+    /// Spans in unknown fonts are skipped.
+    pub fn new(fonts: &[Font<'static>], spans: &[TextSpan], options: &LayoutOptions) -> Self {
+        let mut pending = Vec::new();
+        let mut breaker = LineBreaker::new(options.max_width);
+
+        for (span_index, span) in spans.iter().enumerate() {
+            let font = match fonts.get(span.font.0) {
+                Some(font) => font,
+                None => continue,
+            };
+            let scale = Scale::uniform(span.size);
+            let metrics = LineMetrics::new(font, span.size);
+            breaker.metrics = metrics;
+
+            for c in span.text.chars() {
+                match c {
+                    '\r' => continue,
+                    '\n' => {
+                        breaker.newline(&mut pending);
+                        continue;
+                    }
+                    _ => (),
+                }
+
+                let glyph = font.glyph(c);
+                let id = glyph.id();
+                let advance = glyph.scaled(scale).h_metrics().advance_width;
+                // Only kern between glyphs of the same font and size
+                let kerning = match breaker.previous() {
+                    Some(previous) if previous.font == span.font && previous.size == span.size => {
+                        font.pair_kerning(scale, previous.id, id)
+                    }
+                    _ => 0.0,
+                };
+
+                let glyph = PlacedGlyph {
+                    id,
+                    font: span.font,
+                    size: span.size,
+                    span: span_index,
+                    x: 0.0,
+                    y: 0.0,
+                    advance,
+                };
+                breaker.push(glyph, metrics, c.is_whitespace(), kerning, &mut pending);
+            }
+        }
+
+        breaker.newline(&mut pending);
+
+        let block_width = options
+            .max_width
+            .unwrap_or_else(|| pending.iter().map(|line| line.width).fold(0.0, f32::max));
+
+        let mut glyphs = Vec::new();
+        let mut lines: Vec<LineLayout> = Vec::new();
+        let mut decorations = Vec::new();
+
+        for line in pending {
+            let x = match options.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => (block_width - line.width) / 2.0,
+                TextAlign::Right => block_width - line.width,
+            };
+
+            // Tall text on a line pushes it further away from the one above
+            let baseline = match lines.last() {
+                Some(previous) => {
+                    let line_gap = previous.metrics.line_gap.max(line.metrics.line_gap);
+                    let distance = line.metrics.ascent - previous.metrics.descent + line_gap;
+                    previous.baseline - distance * options.line_spacing
+                }
+                None => 0.0,
+            };
+
+            let first = glyphs.len();
+            glyphs.extend(line.glyphs.iter().map(|glyph| PlacedGlyph {
+                x: x + glyph.x,
+                y: baseline,
+                ..*glyph
+            }));
+            decorate(&glyphs[first..], spans, x + line.width, &mut decorations);
+
+            lines.push(LineLayout {
+                x,
+                width: line.width,
+                baseline,
+                metrics: line.metrics,
+            });
+        }
+
+        return TextLayout {
+            glyphs,
+            lines,
+            decorations,
+        };
+    }
+
+    /// The box spanned by the lines, from the ascent of the first to the descent of the last.
+    pub fn get_bounds(&self) -> Rect<f32> {
+        let left = self.lines.iter().map(|line| line.x).fold(0.0, f32::min);
+        let right = self
+            .lines
+            .iter()
+            .map(|line| line.x + line.width)
+            .fold(0.0, f32::max);
+        let top = self.lines.first().map_or(0.0, |line| line.metrics.ascent);
+        let bottom = self
+            .lines
+            .last()
+            .map_or(0.0, |line| line.baseline + line.metrics.descent);
+
+        return Rect::new(left, bottom, right - left, top - bottom);
+    }
+}
+
+/// Adds the underlines and strikethroughs for one line of glyphs. They stop at `line_end` so
+/// trailing whitespace stays bare.
+fn decorate(
+    glyphs: &[PlacedGlyph],
+    spans: &[TextSpan],
+    line_end: f32,
+    decorations: &mut Vec<Decoration>,
+) {
+    let mut start = 0;
+    while start < glyphs.len() {
+        let span_index = glyphs[start].span;
+        let end = glyphs[start..]
+            .iter()
+            .position(|glyph| glyph.span != span_index)
+            .map_or(glyphs.len(), |offset| start + offset);
+
+        let span = &spans[span_index];
+        let first = &glyphs[start];
+        let last = &glyphs[end - 1];
+        let x1 = first.x;
+        let x2 = (last.x + last.advance).min(line_end);
+
+        // rusttype does not expose the font's own decoration metrics, so use typical proportions
+        let thickness = (span.size / 16.0).max(1.0);
+        let mut offsets = Vec::new();
+        if span.underline {
+            offsets.push(-span.size * 0.1);
+        }
+        if span.strikethrough {
+            offsets.push(span.size * 0.25);
+        }
+
+        if x2 > x1 {
+            for offset in offsets {
+                decorations.push(Decoration {
+                    span: span_index,
+                    rect: Rect::new(x1, first.y + offset - thickness / 2.0, x2 - x1, thickness),
+                });
+            }
+        }
+
+        start = end;
+    }
+}
+
+struct PendingLine {
+    // Glyphs with x relative to the start of the line
+    glyphs: Vec<PlacedGlyph>,
+    width: f32,
+    metrics: LineMetrics,
+}
+
+/// Greedy line breaking, preferring to break after whitespace. Words wider than a line are broken
+/// between glyphs.
+struct LineBreaker {
+    max_width: Option<f32>,
+    glyphs: Vec<(PlacedGlyph, LineMetrics)>,
+    // Pen position
+    x: f32,
+    // Pen position after the last non-whitespace glyph
+    width: f32,
+    // Index and pen position just after the last whitespace, where the line can be broken,
+    // and the width the line has when broken there
+    last_break: Option<(usize, f32, f32)>,
+    // Metrics of the current span, for lines without any glyphs
+    metrics: LineMetrics,
+}
+
+impl LineBreaker {
+    fn new(max_width: Option<f32>) -> Self {
+        return LineBreaker {
+            max_width,
+            glyphs: Vec::new(),
+            x: 0.0,
+            width: 0.0,
+            last_break: None,
+            metrics: LineMetrics {
+                ascent: 0.0,
+                descent: 0.0,
+                line_gap: 0.0,
+            },
+        };
+    }
+
+    fn previous(&self) -> Option<&PlacedGlyph> {
+        return self.glyphs.last().map(|(glyph, _)| glyph);
+    }
+
+    fn push(
+        &mut self,
+        mut glyph: PlacedGlyph,
+        metrics: LineMetrics,
+        whitespace: bool,
+        kerning: f32,
+        lines: &mut Vec<PendingLine>,
+    ) {
+        let mut x = self.x + kerning;
+
+        // Whitespace may hang past the edge, it is trimmed from the line width anyway
+        let max_width = self.max_width.unwrap_or(f32::INFINITY);
+        let overflows = x + glyph.advance > max_width;
+        if !whitespace && overflows && !self.glyphs.is_empty() {
+            match self.last_break.take() {
+                Some((index, break_x, width)) => {
+                    // The word moves to the start of the next line, without its kerning against the
+                    // whitespace before it
+                    let start = self.glyphs.get(index).map_or(break_x, |(glyph, _)| glyph.x);
+                    let rest: Vec<_> = self
+                        .glyphs
+                        .drain(index..)
+                        .map(|(glyph, metrics)| {
+                            let glyph = PlacedGlyph {
+                                x: glyph.x - start,
+                                ..glyph
+                            };
+                            return (glyph, metrics);
+                        })
+                        .collect();
+                    x = if rest.is_empty() { 0.0 } else { x - start };
+                    self.width = width;
+                    self.newline(lines);
+                    self.glyphs = rest;
+
+                    // A word wider than the line is broken at the glyph that does not fit
+                    if !self.glyphs.is_empty() && x + glyph.advance > max_width {
+                        self.width = x - kerning;
+                        self.newline(lines);
+                        x = 0.0;
+                    }
+                }
+                None => {
+                    self.newline(lines);
+                    x = 0.0;
+                }
+            }
+        }
+
+        glyph.x = x;
+        self.x = x + glyph.advance;
+        self.glyphs.push((glyph, metrics));
+
+        if whitespace {
+            self.last_break = Some((self.glyphs.len(), self.x, self.width));
+        } else {
+            self.width = self.x;
+        }
+    }
+
+    /// Ends the current line.
+    fn newline(&mut self, lines: &mut Vec<PendingLine>) {
+        let metrics = self
+            .glyphs
+            .iter()
+            .map(|&(_, metrics)| metrics)
+            .reduce(LineMetrics::max)
+            .unwrap_or(self.metrics);
+        let glyphs = self.glyphs.drain(..).map(|(glyph, _)| glyph).collect();
+
+        lines.push(PendingLine {
+            glyphs,
+            width: self.width,
+            metrics,
+        });
+        self.x = 0.0;
+        self.width = 0.0;
+        self.last_break = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fonts() -> Vec<Font<'static>> {
+        return vec![Font::from_bytes(&include_bytes!("../GoudyStMTT.ttf")[..]).unwrap()];
+    }
+
+    fn span(text: &str, size: f32) -> TextSpan {
+        return TextSpan::new(text, FontId(0), size, Color::from_rgba(0, 0, 0, 255));
+    }
+
+    fn layout(text: &str, options: &LayoutOptions) -> TextLayout {
+        return TextLayout::new(&fonts(), &[span(text, 20.0)], options);
+    }
+
+    #[test]
+    fn newlines_start_lines_one_line_height_apart() {
+        let layout = layout("ab\ncd\r\n", &LayoutOptions::new());
+        let line_height = layout.lines[0].metrics.get_line_height();
+
+        assert_eq!(layout.lines.len(), 3);
+        assert!((layout.lines[1].baseline + line_height).abs() < 0.01);
+        assert_eq!(layout.lines[2].width, 0.0);
+        assert_eq!(layout.glyphs.len(), 4);
+        assert_eq!(layout.glyphs[2].x, 0.0);
+    }
+
+    #[test]
+    fn wrapping_keeps_words_whole() {
+        let unwrapped = layout("aaa aaa", &LayoutOptions::new());
+        let word_width = layout("aaa", &LayoutOptions::new()).lines[0].width;
+
+        let mut options = LayoutOptions::new();
+        options.max_width = Some(unwrapped.lines[0].width - 1.0);
+        let wrapped = layout("aaa aaa", &options);
+
+        assert_eq!(wrapped.lines.len(), 2);
+        assert!((wrapped.lines[0].width - word_width).abs() < 0.01);
+        assert!((wrapped.lines[1].width - word_width).abs() < 0.01);
+        assert_eq!(wrapped.glyphs[4].x, 0.0);
+    }
+
+    #[test]
+    fn long_words_break_anywhere() {
+        let mut options = LayoutOptions::new();
+        options.max_width = Some(25.0);
+        let layout = layout("mmmmmmmm", &options);
+
+        assert!(layout.lines.len() > 1);
+        assert!(layout.lines.iter().all(|line| line.width <= 25.0));
+    }
+
+    #[test]
+    fn long_words_after_a_break_are_broken_too() {
+        let mut options = LayoutOptions::new();
+        options.max_width = Some(25.0);
+        // "i iiii" fits, so the word only moves to the next line at the first "m", where it still does not fit
+        let layout = layout("i iiiimmmm", &options);
+
+        assert!(layout.lines.len() > 2);
+        assert!(layout.lines.iter().all(|line| line.width <= 25.0));
+        assert_eq!(layout.glyphs.len(), 10);
+    }
+
+    #[test]
+    fn alignment_offsets_lines() {
+        let mut options = LayoutOptions::new();
+        options.max_width = Some(200.0);
+        options.align = TextAlign::Right;
+        let right = layout("abc", &options);
+        assert!((right.lines[0].x + right.lines[0].width - 200.0).abs() < 0.01);
+
+        options.align = TextAlign::Center;
+        let center = layout("abc", &options);
+        assert!((center.lines[0].x * 2.0 + center.lines[0].width - 200.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn bounds_span_ascent_to_descent() {
+        let mut options = LayoutOptions::new();
+        options.line_spacing = 2.0;
+        let layout = layout("a\nb", &options);
+        let bounds = layout.get_bounds();
+        let metrics = layout.lines[0].metrics;
+
+        assert_eq!(bounds.x, 0.0);
+        assert!((bounds.y - (-metrics.get_line_height() * 2.0 + metrics.descent)).abs() < 0.01);
+        assert!((bounds.y + bounds.height - metrics.ascent).abs() < 0.01);
+    }
+
+    #[test]
+    fn spans_share_lines_and_the_tallest_sets_the_metrics() {
+        let spans = [
+            span("small ", 10.0),
+            span("BIG", 30.0),
+            span("\nsmall", 10.0),
+        ];
+        let layout = TextLayout::new(&fonts(), &spans, &LayoutOptions::new());
+
+        assert_eq!(layout.lines.len(), 2);
+        assert_eq!(layout.glyphs[6].span, 1);
+        assert!(layout.glyphs[6].x > layout.glyphs[5].x);
+
+        let small = LineMetrics::new(&fonts()[0], 10.0);
+        let big = LineMetrics::new(&fonts()[0], 30.0);
+        assert_eq!(layout.lines[0].metrics.ascent, big.ascent);
+        assert_eq!(layout.lines[1].metrics.ascent, small.ascent);
+        let distance = small.ascent - big.descent + big.line_gap;
+        assert!((layout.lines[1].baseline + distance).abs() < 0.01);
+    }
+
+    #[test]
+    fn decorations_cover_their_span_without_trailing_space() {
+        let spans = [
+            span("plain ", 20.0),
+            span("linked", 20.0).underline(),
+            span(" gone ", 20.0).strikethrough(),
+        ];
+        let layout = TextLayout::new(&fonts(), &spans, &LayoutOptions::new());
+
+        assert_eq!(layout.decorations.len(), 2);
+        let underline = &layout.decorations[0];
+        assert_eq!(underline.span, 1);
+        assert_eq!(underline.rect.x, layout.glyphs[6].x);
+        assert!(underline.rect.y < 0.0);
+
+        let strikethrough = &layout.decorations[1];
+        assert!(strikethrough.rect.y > 0.0);
+        let line = &layout.lines[0];
+        assert!(
+            (strikethrough.rect.x + strikethrough.rect.width - (line.x + line.width)).abs() < 0.01
+        );
+    }
+}
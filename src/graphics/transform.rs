@@ -0,0 +1,155 @@
+use crate::primitives::Point;
+
+/// A 2D affine transform, mapping (x, y) to (a x + c y + tx, b x + d y + ty).
+///
+/// `translate`, `rotate` and `scale` apply to the coordinates going in, so
+/// `Transform::identity().translate(10.0, 0.0).rotate(angle)` rotates a shape around its own origin and then
+/// moves it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub tx: f32,
+    pub ty: f32,
+}
+
+impl Transform {
+    /// An arbitrary affine transform, the first column of the matrix being (`a`, `b`), the second (`c`, `d`)
+    /// and the translation (`tx`, `ty`).
+    pub fn new(a: f32, b: f32, c: f32, d: f32, tx: f32, ty: f32) -> Self {
+        return Transform { a, b, c, d, tx, ty };
+    }
+
+    pub fn identity() -> Self {
+        return Transform::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+    }
+
+    pub fn translation(x: f32, y: f32) -> Self {
+        return Transform::new(1.0, 0.0, 0.0, 1.0, x, y);
+    }
+
+    /// Counter-clockwise by `angle` radians, y pointing up.
+    pub fn rotation(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        return Transform::new(cos, sin, -sin, cos, 0.0, 0.0);
+    }
+
+    pub fn scaling(x: f32, y: f32) -> Self {
+        return Transform::new(x, 0.0, 0.0, y, 0.0, 0.0);
+    }
+
+    pub fn translate(self, x: f32, y: f32) -> Self {
+        return self.then(&Transform::translation(x, y));
+    }
+
+    pub fn rotate(self, angle: f32) -> Self {
+        return self.then(&Transform::rotation(angle));
+    }
+
+    pub fn scale(self, x: f32, y: f32) -> Self {
+        return self.then(&Transform::scaling(x, y));
+    }
+
+    /// Applies `inner` first and then this transform.
+    pub fn then(&self, inner: &Transform) -> Self {
+        return Transform::new(
+            self.a * inner.a + self.c * inner.b,
+            self.b * inner.a + self.d * inner.b,
+            self.a * inner.c + self.c * inner.d,
+            self.b * inner.c + self.d * inner.d,
+            self.a * inner.tx + self.c * inner.ty + self.tx,
+            self.b * inner.tx + self.d * inner.ty + self.ty,
+        );
+    }
+
+    /// The transform undoing this one, unless it collapses everything onto a line or a point.
+    pub fn inverse(&self) -> Option<Self> {
+        let determinant = self.determinant();
+        if determinant.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let a = self.d / determinant;
+        let b = -self.b / determinant;
+        let c = -self.c / determinant;
+        let d = self.a / determinant;
+        return Some(Transform::new(
+            a,
+            b,
+            c,
+            d,
+            -(a * self.tx + c * self.ty),
+            -(b * self.tx + d * self.ty),
+        ));
+    }
+
+    pub fn transform_point(&self, point: &Point<f32>) -> Point<f32> {
+        return Point::new(
+            self.a * point.x + self.c * point.y + self.tx,
+            self.b * point.x + self.d * point.y + self.ty,
+        );
+    }
+
+    /// How much areas are scaled by, as a length: 1 for rotations and translations, 2 for doubling both axes.
+    pub fn get_scale(&self) -> f32 {
+        return self.determinant().abs().sqrt();
+    }
+
+    /// Whether axis-aligned rectangles stay axis-aligned, which holds for scaling and quarter turns.
+    pub fn is_axis_aligned(&self) -> bool {
+        let tolerance = 1e-6;
+        return (self.b.abs() < tolerance && self.c.abs() < tolerance)
+            || (self.a.abs() < tolerance && self.d.abs() < tolerance);
+    }
+
+    fn determinant(&self) -> f32 {
+        return self.a * self.d - self.b * self.c;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Point<f32>, expected: Point<f32>) {
+        assert!(
+            (actual.x - expected.x).abs() < 0.0001 && (actual.y - expected.y).abs() < 0.0001,
+            "{:?} is not {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn builders_apply_to_local_coordinates() {
+        let transform = Transform::identity()
+            .translate(10.0, 5.0)
+            .rotate(std::f32::consts::PI / 2.0)
+            .scale(2.0, 1.0);
+
+        // Scaled to (2, 0), rotated to (0, 2), moved to (10, 7)
+        assert_near(
+            transform.transform_point(&Point::new(1.0, 0.0)),
+            Point::new(10.0, 7.0),
+        );
+        assert!((transform.get_scale() - 2.0_f32.sqrt()).abs() < 0.0001);
+        assert!(transform.is_axis_aligned());
+        assert!(!transform.rotate(0.1).is_axis_aligned());
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform = Transform::new(2.0, 1.0, -1.0, 3.0, 4.0, -5.0);
+        let inverse = transform.inverse().unwrap();
+        let point = Point::new(3.0, 7.0);
+
+        assert_near(
+            inverse.transform_point(&transform.transform_point(&point)),
+            point,
+        );
+        assert_near(transform.then(&inverse).transform_point(&point), point);
+        assert!(Transform::scaling(0.0, 1.0).inverse().is_none());
+    }
+}
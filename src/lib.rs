@@ -1,29 +1,174 @@
+#![allow(clippy::needless_return, clippy::new_without_default)]
+
 extern crate gl;
 extern crate glutin;
+extern crate image;
+#[cfg(target_os = "linux")]
+extern crate libc;
 extern crate nalgebra;
 extern crate rusttype;
 
 pub mod graphics;
 pub mod primitives;
+pub mod resources;
 pub mod subsystems;
-//mod resources;
+pub mod testing;
 
 use self::graphics::Graphics;
-use crate::subsystems::{
-    Event,
-    Window,
+use crate::{
+    resources::ResourceManager,
+    subsystems::{
+        Event,
+        InputState,
+        Window,
+    },
 };
+use std::path::PathBuf;
 
+#[derive(Debug)]
 pub enum SlashError {
     NoWindow,
+    /// The window or its GL context could not be created.
+    WindowCreation(glutin::CreationError),
+    /// The GL context could not be made current or could not present a frame.
+    Context(glutin::ContextError),
+    /// No GL context could be made for `Backend::Headless`.
+    Headless(String),
+    /// The offscreen framebuffer `Backend::Headless` draws into cannot be rendered to.
+    IncompleteFramebuffer,
+    /// A shader stage failed to compile. `name` identifies the source, `log` is the driver's info log.
+    ShaderCompile {
+        name: String,
+        log: String,
+    },
+    /// A shader program failed to link.
+    ShaderLink {
+        log: String,
+    },
+    Font(rusttype::Error),
+    Io(std::io::Error),
+    Image(image::ImageError),
+    /// A resource file could not be loaded.
+    Resource {
+        path: PathBuf,
+        error: Box<SlashError>,
+    },
+    /// A resource name was registered again with another file. `path` is the file it already names.
+    ResourceNameTaken {
+        name: String,
+        path: PathBuf,
+    },
+}
+
+impl std::fmt::Display for SlashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SlashError::NoWindow => write!(f, "no window has been created"),
+            SlashError::WindowCreation(e) => write!(f, "failed to create the window: {}", e),
+            SlashError::Context(e) => write!(f, "GL context error: {}", e),
+            SlashError::Headless(message) => {
+                write!(f, "failed to create a headless GL context: {}", message)
+            }
+            SlashError::IncompleteFramebuffer => {
+                write!(f, "the offscreen framebuffer is incomplete")
+            }
+            SlashError::ShaderCompile { name, log } => {
+                write!(f, "failed to compile shader '{}': {}", name, log)
+            }
+            SlashError::ShaderLink { log } => write!(f, "failed to link shader program: {}", log),
+            SlashError::Font(e) => write!(f, "failed to load font: {}", e),
+            SlashError::Io(e) => write!(f, "{}", e),
+            SlashError::Image(e) => write!(f, "failed to decode image: {}", e),
+            SlashError::Resource { path, error } => {
+                write!(f, "failed to load '{}': {}", path.display(), error)
+            }
+            SlashError::ResourceNameTaken { name, path } => write!(
+                f,
+                "resource name '{}' is already registered for '{}'",
+                name,
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SlashError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SlashError::WindowCreation(e) => Some(e),
+            SlashError::Context(e) => Some(e),
+            SlashError::Font(e) => Some(e),
+            SlashError::Io(e) => Some(e),
+            SlashError::Image(e) => Some(e),
+            SlashError::Resource { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<glutin::CreationError> for SlashError {
+    fn from(e: glutin::CreationError) -> Self {
+        SlashError::WindowCreation(e)
+    }
+}
+
+impl From<glutin::ContextError> for SlashError {
+    fn from(e: glutin::ContextError) -> Self {
+        SlashError::Context(e)
+    }
+}
+
+impl From<std::io::Error> for SlashError {
+    fn from(e: std::io::Error) -> Self {
+        SlashError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for SlashError {
+    fn from(e: image::ImageError) -> Self {
+        SlashError::Image(e)
+    }
+}
+
+impl From<rusttype::Error> for SlashError {
+    fn from(e: rusttype::Error) -> Self {
+        SlashError::Font(e)
+    }
 }
 
 pub type SlashResult<T> = Result<T, SlashError>;
 
+/// Where the app renders its frames.
+pub enum Backend {
+    /// A regular on-screen window.
+    Windowed,
+    /// An offscreen framebuffer with no visible window, for CI and build servers.
+    Headless,
+}
+
+/// How drawing coordinates follow the window when it is resized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalingPolicy {
+    /// Keep the size from `AppState` as the drawing area and stretch it over the whole window.
+    Stretch,
+    /// Keep the size from `AppState` and scale it uniformly to fit, leaving bars in the clear color.
+    Letterbox,
+    /// Grow or shrink the drawing area with the window, one unit per pixel.
+    Expand,
+}
+
 pub struct AppState {
     pub width: f64,
     pub height: f64,
     pub title: String,
+    pub backend: Backend,
+    /// The directory relative resource paths are resolved against.
+    pub asset_dir: PathBuf,
+    /// Reload assets from `asset_dir` when they change on disk.
+    pub hot_reload: bool,
+    /// Whether the user can resize the window.
+    pub resizable: bool,
+    pub scaling: ScalingPolicy,
 }
 
 impl AppState {
@@ -32,6 +177,11 @@ impl AppState {
             width: 1920.0,
             height: 1080.0,
             title: String::from("Slash"),
+            backend: Backend::Windowed,
+            asset_dir: PathBuf::from("."),
+            hot_reload: false,
+            resizable: false,
+            scaling: ScalingPolicy::Expand,
         };
     }
 }
@@ -39,11 +189,12 @@ impl AppState {
 pub struct App<'a> {
     graphics: Graphics,
     window: Window,
+    resources: ResourceManager,
 
     pub app_state: AppState,
 
     pub running: bool,
-    pub state: Box<State + 'a>,
+    pub state: Box<dyn State + 'a>,
 }
 
 impl<'a> App<'a> {
@@ -52,6 +203,7 @@ impl<'a> App<'a> {
         return App {
             graphics: Graphics::new(),
             window: Window::new(),
+            resources: ResourceManager::new(),
 
             app_state,
 
@@ -68,31 +220,52 @@ impl<'a> App<'a> {
         self.state = Box::new(state);
     }
 
-    pub fn init(&mut self) {
-        self.window.init(&self.app_state);
+    pub fn init(&mut self) -> SlashResult<()> {
+        self.window.init(&self.app_state)?;
         self.graphics
-            .init(self.app_state.width as f32, self.app_state.height as f32);
-        self.state.init(&mut self.window, &mut self.graphics);
+            .init(&self.app_state, self.window.get_dpi_factor())?;
+        self.resources.set_root(self.app_state.asset_dir.clone());
+        self.resources.set_hot_reload(self.app_state.hot_reload);
+        self.state
+            .init(&mut self.window, &mut self.graphics, &mut self.resources);
         self.running = true;
+
+        return Ok(());
+    }
+
+    /// Saves the frame drawn by the last call to `main_loop`. The format is picked from the file extension.
+    pub fn save_screenshot<P: AsRef<std::path::Path>>(&mut self, path: P) -> SlashResult<()> {
+        self.graphics.capture().save(path)?;
+        return Ok(());
     }
 
     pub fn main_loop(&mut self) -> SlashResult<()> {
-        self.window.update();
+        self.window.update()?;
+        for error in self.resources.update() {
+            eprintln!("Hot reload failed: {}", error);
+        }
+        if let Some(sprite_renderer) = self.graphics.sprite_renderer.as_mut() {
+            sprite_renderer.refresh_fonts(&self.resources);
+        }
 
         let state = &mut self.state;
 
+        self.window.begin_frame();
         while let Some(event) = self.window.handle_event() {
             match event {
                 Event::Close => self.running = false,
-                _ => {}
+                Event::Resized { width, height } => self.graphics.resize(width, height)?,
+                Event::ScaleFactorChanged { factor } => self.graphics.set_dpi_factor(factor)?,
+                _ => (),
             }
             state.handle_event(&event, &self.window);
         }
 
         self.graphics.clear();
 
-        state.update(&self.app_state);
+        state.update(&self.app_state, self.window.get_input());
         state.render(&mut self.graphics, &self.app_state);
+        self.graphics.flush();
 
         return Ok(());
     }
@@ -102,10 +275,16 @@ pub trait State {
     fn new() -> Self
     where
         Self: Sized;
-    fn init(&mut self, window: &mut Window, graphics: &mut Graphics) {}
-    fn handle_event(&mut self, event: &Event, window: &Window) {}
-    fn update(&mut self, _state: &AppState) {}
-    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {}
+    fn init(
+        &mut self,
+        _window: &mut Window,
+        _graphics: &mut Graphics,
+        _resources: &mut ResourceManager,
+    ) {
+    }
+    fn handle_event(&mut self, _event: &Event, _window: &Window) {}
+    fn update(&mut self, _state: &AppState, _input: &InputState) {}
+    fn render(&mut self, _graphics: &mut Graphics, _state: &AppState) {}
 }
 
 struct DefaultState;
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Point<T> {
     pub x: T,
     pub y: T,
@@ -10,7 +10,7 @@ impl<T> Point<T> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rect<T> {
     pub x: T,
     pub y: T,
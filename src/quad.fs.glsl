@@ -1,8 +0,0 @@
-#version 330 core
-
-precision highp float;
-uniform vec4 in_color;
-
-void main() {
-    gl_FragColor = in_color;
-}
\ No newline at end of file
@@ -1,43 +1,708 @@
-use std::collections::HashMap;
+use crate::{
+    graphics::{
+        ShaderProgram,
+        Texture,
+        TextureFormat,
+    },
+    SlashError,
+    SlashResult,
+};
+use image::RgbaImage;
+use rusttype::Font;
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    marker::PhantomData,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        Instant,
+        SystemTime,
+    },
+};
 
-pub enum Resource{
-	Image{
-		width: u32,
-		height: u32,
-		data: Vec<u8>,
-	},
+/// The source code of a single GLSL shader stage.
+pub struct ShaderSource(pub String);
+
+pub enum Resource {
+    Image(RgbaImage),
+    Font(Font<'static>),
+    Shader(ShaderSource),
+}
+
+/// A type that can be loaded from disk by the `ResourceManager`.
+pub trait ResourceKind: Sized + 'static {
+    fn load(path: &Path) -> SlashResult<Resource>;
+    fn from_resource(resource: &Resource) -> Option<&Self>;
+}
+
+impl ResourceKind for RgbaImage {
+    fn load(path: &Path) -> SlashResult<Resource> {
+        return Ok(Resource::Image(image::open(path)?.to_rgba()));
+    }
+
+    fn from_resource(resource: &Resource) -> Option<&Self> {
+        match resource {
+            Resource::Image(image) => Some(image),
+            _ => None,
+        }
+    }
+}
+
+impl ResourceKind for Font<'static> {
+    fn load(path: &Path) -> SlashResult<Resource> {
+        let data = std::fs::read(path)?;
+        return Ok(Resource::Font(Font::from_bytes(data)?));
+    }
+
+    fn from_resource(resource: &Resource) -> Option<&Self> {
+        match resource {
+            Resource::Font(font) => Some(font),
+            _ => None,
+        }
+    }
+}
+
+impl ResourceKind for ShaderSource {
+    fn load(path: &Path) -> SlashResult<Resource> {
+        return Ok(Resource::Shader(ShaderSource(std::fs::read_to_string(
+            path,
+        )?)));
+    }
+
+    fn from_resource(resource: &Resource) -> Option<&Self> {
+        match resource {
+            Resource::Shader(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// A typed reference to a resource registered with a `ResourceManager`.
+pub struct Handle<T> {
+    index: usize,
+    _kind: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(index: usize) -> Self {
+        Handle {
+            index,
+            _kind: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> std::hash::Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+pub type ImageHandle = Handle<RgbaImage>;
+pub type FontHandle = Handle<Font<'static>>;
+pub type ShaderHandle = Handle<ShaderSource>;
+pub type TextureHandle = Handle<Texture>;
+pub type ProgramHandle = Handle<ShaderProgram>;
+
+fn get_modified(path: &Path) -> Option<SystemTime> {
+    return std::fs::metadata(path).and_then(|m| m.modified()).ok();
+}
+
+struct ResourceEntry {
+    name: String,
+    path: PathBuf,
+    data: Option<Resource>,
+    modified: Option<SystemTime>,
+    generation: u64,
+}
+
+struct TextureEntry {
+    image: ImageHandle,
+    texture: Texture,
+    generation: u64,
 }
 
-pub struct ResourceHandle{
-	name: String,
-	path: String,
-	data: Option<Box<Resource>>,
+struct ProgramEntry {
+    vertex: ShaderHandle,
+    fragment: ShaderHandle,
+    program: ShaderProgram,
+    generations: (u64, u64),
 }
 
-impl ResourceHandle{
+/// Loads assets from disk by name and caches them.
+/// Relative paths are resolved against the root directory, the `asset_dir` of the `AppState` when owned by an `App`.
+///
+/// With hot reloading enabled, `update` polls the modification times of loaded files and reloads the ones that changed,
+/// re-uploading textures and relinking programs built from them. Fonts registered with
+/// `SpriteRenderer::add_font_resource` are swapped in by `App` after each update.
+pub struct ResourceManager {
+    root: PathBuf,
+    entries: Vec<ResourceEntry>,
+    // Names are per kind, an image and a font may both be called "ui"
+    map: HashMap<(String, TypeId), usize>,
+
+    textures: Vec<TextureEntry>,
+    programs: Vec<ProgramEntry>,
 
+    hot_reload: bool,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+    reload_errors: Vec<SlashError>,
 }
 
-pub struct ResourceManager{
-	map: HashMap<String, ResourceHandle>,
+impl ResourceManager {
+    pub fn new() -> Self {
+        ResourceManager {
+            root: PathBuf::new(),
+            entries: Vec::new(),
+            map: HashMap::new(),
+
+            textures: Vec::new(),
+            programs: Vec::new(),
+
+            hot_reload: false,
+            poll_interval: Duration::from_millis(500),
+            last_poll: None,
+            reload_errors: Vec::new(),
+        }
+    }
+
+    pub fn set_hot_reload(&mut self, hot_reload: bool) {
+        self.hot_reload = hot_reload;
+    }
+
+    pub fn is_hot_reload(&self) -> bool {
+        return self.hot_reload;
+    }
+
+    /// Sets how often `update` checks files for changes.
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    pub fn set_root<P: Into<PathBuf>>(&mut self, root: P) {
+        self.root = root.into();
+    }
+
+    pub fn get_root(&self) -> &Path {
+        return &self.root;
+    }
+
+    /// Registers a resource without loading it. Registering a name twice for the same kind and file returns the
+    /// existing handle, registering it for another file fails. Each kind has its own names.
+    pub fn add_resource<T: ResourceKind, P: AsRef<Path>>(
+        &mut self,
+        name: &str,
+        path: P,
+    ) -> SlashResult<Handle<T>> {
+        let path = self.root.join(path);
+        let key = (String::from(name), TypeId::of::<T>());
+        if let Some(&index) = self.map.get(&key) {
+            let entry = &self.entries[index];
+            if entry.path != path {
+                return Err(SlashError::ResourceNameTaken {
+                    name: String::from(name),
+                    path: entry.path.clone(),
+                });
+            }
+            return Ok(Handle::new(index));
+        }
+
+        let index = self.entries.len();
+        self.entries.push(ResourceEntry {
+            name: String::from(name),
+            path,
+            data: None,
+            modified: None,
+            generation: 0,
+        });
+        self.map.insert(key, index);
+
+        return Ok(Handle::new(index));
+    }
+
+    /// Loads a registered resource if it is not cached yet.
+    pub fn load_resource<T: ResourceKind>(&mut self, handle: Handle<T>) -> SlashResult<&T> {
+        let entry = &mut self.entries[handle.index];
+
+        if entry.data.is_none() {
+            entry.modified = get_modified(&entry.path);
+            entry.data = Some(T::load(&entry.path).map_err(|e| SlashError::Resource {
+                path: entry.path.clone(),
+                error: Box::new(e),
+            })?);
+            entry.generation += 1;
+        }
+
+        // Handles only come from `add_resource`, which keeps every kind's entries apart
+        return Ok(entry
+            .data
+            .as_ref()
+            .and_then(T::from_resource)
+            .expect("Handle points at a resource of another kind"));
+    }
+
+    /// Returns a resource if it has been loaded.
+    pub fn get_resource<T: ResourceKind>(&self, handle: Handle<T>) -> Option<&T> {
+        return self.entries[handle.index]
+            .data
+            .as_ref()
+            .and_then(T::from_resource);
+    }
+
+    pub fn get_handle<T: ResourceKind>(&self, name: &str) -> Option<Handle<T>> {
+        return self
+            .map
+            .get(&(String::from(name), TypeId::of::<T>()))
+            .map(|&index| Handle::new(index));
+    }
+
+    pub fn get_name<T: ResourceKind>(&self, handle: Handle<T>) -> &str {
+        return &self.entries[handle.index].name;
+    }
+
+    pub fn get_path<T: ResourceKind>(&self, handle: Handle<T>) -> &Path {
+        return &self.entries[handle.index].path;
+    }
+
+    /// A counter that goes up every time the resource is (re)loaded, so users can tell when to rebuild derived data.
+    pub fn get_generation<T: ResourceKind>(&self, handle: Handle<T>) -> u64 {
+        return self.entries[handle.index].generation;
+    }
+
+    /// Drops the cached data of a resource. The handle stays valid and the next load reads the file again.
+    pub fn unload_resource<T: ResourceKind>(&mut self, handle: Handle<T>) {
+        self.entries[handle.index].data = None;
+    }
+
+    pub fn load_image<P: AsRef<Path>>(&mut self, name: &str, path: P) -> SlashResult<ImageHandle> {
+        let handle = self.add_resource(name, path)?;
+        self.load_resource(handle)?;
+        return Ok(handle);
+    }
+
+    pub fn load_font<P: AsRef<Path>>(&mut self, name: &str, path: P) -> SlashResult<FontHandle> {
+        let handle = self.add_resource(name, path)?;
+        self.load_resource(handle)?;
+        return Ok(handle);
+    }
+
+    pub fn load_shader<P: AsRef<Path>>(
+        &mut self,
+        name: &str,
+        path: P,
+    ) -> SlashResult<ShaderHandle> {
+        let handle = self.add_resource(name, path)?;
+        self.load_resource(handle)?;
+        return Ok(handle);
+    }
 }
 
-impl ResourceManager{
-	pub fn new() -> Self{
-		ResourceManager{
-			map: HashMap::new(),
-		}
-	}
-	
-	pub fn add_resource(&mut self, name: String, path: String){
-		
-	}
-	
-	pub fn load_resource(&mut self, name: &str){
-		
-	}
-	
-	pub fn get_resource(&mut self, name: &str){
-	
-	}
-}
\ No newline at end of file
+impl ResourceManager {
+    /// Loads an image and uploads it as a texture that follows the image across reloads.
+    pub fn load_texture<P: AsRef<Path>>(
+        &mut self,
+        name: &str,
+        path: P,
+    ) -> SlashResult<TextureHandle> {
+        let image = self.load_image(name, path)?;
+
+        if let Some(index) = self.textures.iter().position(|t| t.image == image) {
+            return Ok(Handle::new(index));
+        }
+
+        let texture = Texture::from_image(self.load_resource(image)?);
+        self.textures.push(TextureEntry {
+            image,
+            texture,
+            generation: self.get_generation(image),
+        });
+
+        return Ok(Handle::new(self.textures.len() - 1));
+    }
+
+    pub fn get_texture(&self, handle: TextureHandle) -> &Texture {
+        return &self.textures[handle.index].texture;
+    }
+
+    /// Loads a vertex and fragment shader and links them. The program is relinked when either source changes.
+    /// Uniforms are not carried over to a relinked program, so set them before every use.
+    pub fn load_program<P: AsRef<Path>>(
+        &mut self,
+        name: &str,
+        vertex_path: P,
+        fragment_path: P,
+    ) -> SlashResult<ProgramHandle> {
+        let vertex = self.load_shader(&format!("{}.vs", name), vertex_path)?;
+        let fragment = self.load_shader(&format!("{}.fs", name), fragment_path)?;
+
+        if let Some(index) = self
+            .programs
+            .iter()
+            .position(|p| p.vertex == vertex && p.fragment == fragment)
+        {
+            return Ok(Handle::new(index));
+        }
+
+        let program = ShaderProgram::from_sources(
+            &self.get_path(vertex).display().to_string(),
+            &self.get_resource(vertex).expect("Shader not loaded").0,
+            &self.get_path(fragment).display().to_string(),
+            &self.get_resource(fragment).expect("Shader not loaded").0,
+        )?;
+
+        self.programs.push(ProgramEntry {
+            vertex,
+            fragment,
+            program,
+            generations: (self.get_generation(vertex), self.get_generation(fragment)),
+        });
+
+        return Ok(Handle::new(self.programs.len() - 1));
+    }
+
+    pub fn get_program(&self, handle: ProgramHandle) -> &ShaderProgram {
+        return &self.programs[handle.index].program;
+    }
+
+    /// Reloads every loaded file whose modification time changed, returning how many were reloaded.
+    /// A file that fails to load keeps its previous contents, the error is kept for `take_reload_errors`.
+    pub fn reload_changed(&mut self) -> usize {
+        let mut reloaded = 0;
+
+        for entry in self.entries.iter_mut() {
+            let data = match entry.data.as_ref() {
+                Some(data) => data,
+                None => continue,
+            };
+
+            let modified = get_modified(&entry.path);
+            if modified.is_none() || modified == entry.modified {
+                continue;
+            }
+
+            let result = match data {
+                Resource::Image(_) => RgbaImage::load(&entry.path),
+                Resource::Font(_) => Font::load(&entry.path),
+                Resource::Shader(_) => ShaderSource::load(&entry.path),
+            };
+
+            // Remember the time even on failure so a broken file is not reloaded every poll
+            entry.modified = modified;
+
+            match result {
+                Ok(data) => {
+                    entry.data = Some(data);
+                    entry.generation += 1;
+                    reloaded += 1;
+                }
+                Err(e) => self.reload_errors.push(SlashError::Resource {
+                    path: entry.path.clone(),
+                    error: Box::new(e),
+                }),
+            }
+        }
+
+        return reloaded;
+    }
+
+    /// Polls for changed files if hot reloading is enabled, returning what failed to reload, see
+    /// `take_reload_errors`. Called once per frame by `App`, which prints the failures.
+    pub fn update(&mut self) -> Vec<SlashError> {
+        if !self.hot_reload {
+            return self.take_reload_errors();
+        }
+
+        let now = Instant::now();
+        if let Some(last_poll) = self.last_poll {
+            if now.duration_since(last_poll) < self.poll_interval {
+                return self.take_reload_errors();
+            }
+        }
+        self.last_poll = Some(now);
+
+        if self.reload_changed() > 0 {
+            self.refresh_textures();
+            self.refresh_programs();
+        }
+
+        return self.take_reload_errors();
+    }
+
+    /// The files that failed to reload and the programs that failed to relink since the last call, oldest
+    /// first. Whatever failed keeps its previous contents.
+    pub fn take_reload_errors(&mut self) -> Vec<SlashError> {
+        return std::mem::take(&mut self.reload_errors);
+    }
+
+    fn refresh_textures(&mut self) {
+        let entries = &self.entries;
+
+        for texture_entry in self.textures.iter_mut() {
+            let entry = &entries[texture_entry.image.index];
+            if entry.generation == texture_entry.generation {
+                continue;
+            }
+
+            if let Some(Resource::Image(image)) = entry.data.as_ref() {
+                texture_entry.texture.enable();
+                texture_entry.texture.set(
+                    image.width() as i32,
+                    image.height() as i32,
+                    TextureFormat::Rgba,
+                    image,
+                );
+            }
+
+            texture_entry.generation = entry.generation;
+        }
+    }
+
+    fn refresh_programs(&mut self) {
+        let entries = &self.entries;
+
+        for program_entry in self.programs.iter_mut() {
+            let vertex = &entries[program_entry.vertex.index];
+            let fragment = &entries[program_entry.fragment.index];
+            let generations = (vertex.generation, fragment.generation);
+            if generations == program_entry.generations {
+                continue;
+            }
+            program_entry.generations = generations;
+
+            if let (Some(Resource::Shader(vertex_src)), Some(Resource::Shader(fragment_src))) =
+                (vertex.data.as_ref(), fragment.data.as_ref())
+            {
+                match ShaderProgram::from_sources(
+                    &vertex.path.display().to_string(),
+                    &vertex_src.0,
+                    &fragment.path.display().to_string(),
+                    &fragment_src.0,
+                ) {
+                    Ok(program) => program_entry.program = program,
+                    Err(e) => self.reload_errors.push(e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A directory under the system temp dir, removed with its contents when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "slash-resources-{}-{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            return TempDir(dir);
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            return &self.0;
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn loads_and_caches_by_name() {
+        let dir = TempDir::new("cache");
+        std::fs::write(dir.join("test.vs.glsl"), "void main() {}").unwrap();
+
+        let mut resources = ResourceManager::new();
+        resources.set_root(&*dir);
+
+        let handle = resources.load_shader("test", "test.vs.glsl").unwrap();
+        assert_eq!(resources.get_resource(handle).unwrap().0, "void main() {}");
+
+        // The cached copy is returned even after the file is gone
+        std::fs::remove_file(dir.join("test.vs.glsl")).unwrap();
+        assert_eq!(
+            resources.load_shader("test", "test.vs.glsl").unwrap(),
+            handle
+        );
+        assert_eq!(resources.get_handle::<ShaderSource>("test"), Some(handle));
+        assert_eq!(resources.get_resource(handle).unwrap().0, "void main() {}");
+    }
+
+    #[test]
+    fn loads_images_and_fonts() {
+        let dir = TempDir::new("kinds");
+        let image = RgbaImage::from_pixel(3, 2, image::Rgba([1, 2, 3, 4]));
+        image.save(dir.join("pixel.png")).unwrap();
+        std::fs::write(dir.join("font.ttf"), &include_bytes!("GoudyStMTT.ttf")[..]).unwrap();
+
+        let mut resources = ResourceManager::new();
+        resources.set_root(&*dir);
+
+        let image_handle = resources.load_image("pixel", "pixel.png").unwrap();
+        let loaded = resources.get_resource(image_handle).unwrap();
+        assert_eq!(loaded.dimensions(), (3, 2));
+        assert_eq!(loaded.get_pixel(2, 1), &image::Rgba([1, 2, 3, 4]));
+
+        let font_handle = resources.load_font("font", "font.ttf").unwrap();
+        assert!(resources.get_resource(font_handle).unwrap().glyph_count() > 0);
+    }
+
+    #[test]
+    fn kinds_have_their_own_names() {
+        let dir = TempDir::new("names");
+        RgbaImage::new(2, 2).save(dir.join("ui.png")).unwrap();
+        std::fs::write(dir.join("ui.ttf"), &include_bytes!("GoudyStMTT.ttf")[..]).unwrap();
+
+        let mut resources = ResourceManager::new();
+        resources.set_root(&*dir);
+
+        let image = resources.load_image("ui", "ui.png").unwrap();
+        let font = resources.load_font("ui", "ui.ttf").unwrap();
+        assert_eq!(resources.get_path(image), dir.join("ui.png"));
+        assert_eq!(resources.get_path(font), dir.join("ui.ttf"));
+        assert!(resources.get_resource(font).unwrap().glyph_count() > 0);
+        assert_eq!(resources.get_handle::<RgbaImage>("ui"), Some(image));
+        assert_eq!(resources.get_handle::<ShaderSource>("ui"), None);
+    }
+
+    #[test]
+    fn names_cannot_be_moved_to_another_file() {
+        let dir = TempDir::new("moved");
+        let mut resources = ResourceManager::new();
+        resources.set_root(&*dir);
+
+        let handle: ShaderHandle = resources.add_resource("shader", "a.fs.glsl").unwrap();
+        assert_eq!(
+            resources.add_resource("shader", "a.fs.glsl").unwrap(),
+            handle
+        );
+        match resources.add_resource::<ShaderSource, _>("shader", "b.fs.glsl") {
+            Err(SlashError::ResourceNameTaken { name, path }) => {
+                assert_eq!(name, "shader");
+                assert_eq!(path, dir.join("a.fs.glsl"));
+            }
+            _ => panic!("Registering a name for another file should fail"),
+        }
+        assert_eq!(resources.get_path(handle), dir.join("a.fs.glsl"));
+    }
+
+    #[test]
+    fn resources_load_lazily() {
+        let dir = TempDir::new("lazy");
+        let mut resources = ResourceManager::new();
+        resources.set_root(&*dir);
+
+        let handle: ShaderHandle = resources.add_resource("late", "late.fs.glsl").unwrap();
+        assert!(resources.get_resource(handle).is_none());
+        match resources.load_resource(handle) {
+            Err(SlashError::Resource { path, error }) => {
+                assert_eq!(path, dir.join("late.fs.glsl"));
+                assert!(matches!(*error, SlashError::Io(_)));
+            }
+            _ => panic!("Loading a missing file should fail with its path"),
+        }
+
+        std::fs::write(dir.join("late.fs.glsl"), "// late").unwrap();
+        assert_eq!(resources.load_resource(handle).unwrap().0, "// late");
+    }
+
+    #[test]
+    fn changed_files_are_reloaded() {
+        let dir = TempDir::new("reload");
+        let path = dir.join("reload.fs.glsl");
+        std::fs::write(&path, "// first").unwrap();
+
+        let mut resources = ResourceManager::new();
+        resources.set_root(&*dir);
+
+        let handle = resources.load_shader("reload", "reload.fs.glsl").unwrap();
+        let generation = resources.get_generation(handle);
+        assert_eq!(resources.reload_changed(), 0);
+
+        std::fs::write(&path, "// second").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(10);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        assert_eq!(resources.reload_changed(), 1);
+        assert_eq!(resources.get_resource(handle).unwrap().0, "// second");
+        assert_eq!(resources.get_generation(handle), generation + 1);
+        assert_eq!(resources.reload_changed(), 0);
+    }
+
+    #[test]
+    fn failed_reloads_keep_old_data() {
+        let dir = TempDir::new("broken");
+        let path = dir.join("broken.png");
+        RgbaImage::new(2, 2).save(&path).unwrap();
+
+        let mut resources = ResourceManager::new();
+        resources.set_root(&*dir);
+
+        let handle = resources.load_image("broken", "broken.png").unwrap();
+        std::fs::write(&path, "not a png").unwrap();
+        let later = SystemTime::now() + Duration::from_secs(10);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        assert_eq!(resources.reload_changed(), 0);
+        assert_eq!(resources.get_resource(handle).unwrap().dimensions(), (2, 2));
+
+        let errors = resources.take_reload_errors();
+        assert_eq!(errors.len(), 1);
+        match &errors[0] {
+            SlashError::Resource {
+                path: error_path, ..
+            } => assert_eq!(error_path, &path),
+            e => panic!("Unexpected reload error: {}", e),
+        }
+        assert!(resources.take_reload_errors().is_empty());
+    }
+}
@@ -1,6 +1,34 @@
+pub mod gamepad;
+mod headless;
+mod input;
+
+pub use self::gamepad::{
+    GamepadAxis,
+    GamepadBackend,
+    GamepadButton,
+    GamepadEvent,
+    GamepadId,
+    GamepadState,
+};
+
+pub use self::input::{
+    InputState,
+    Key,
+    Modifiers,
+    MouseButton,
+    ScrollDelta,
+};
+
+use self::{
+    gamepad::NullGamepadBackend,
+    headless::HeadlessContext,
+};
 use crate::{
     primitives::Point,
     AppState,
+    Backend,
+    SlashError,
+    SlashResult,
 };
 use glutin::{
     EventsLoop,
@@ -11,28 +39,36 @@ use std::collections::VecDeque;
 
 pub struct Window {
     window: Option<GlWindow>,
+    headless_context: Option<HeadlessContext>,
     events_loop: Option<EventsLoop>,
 
     mouse_x: f32,
     mouse_y: f32,
     width: f32,
     height: f32,
+    dpi_factor: f64,
 
     event_queue: VecDeque<Event>,
+    input: InputState,
+    gamepads: Box<dyn GamepadBackend>,
 }
 
 impl Window {
     pub fn new() -> Self {
         Window {
             window: None,
+            headless_context: None,
             events_loop: None,
 
             mouse_x: 0.0,
             mouse_y: 0.0,
             width: 0.0,
             height: 0.0,
+            dpi_factor: 1.0,
 
             event_queue: VecDeque::new(),
+            input: InputState::new(),
+            gamepads: Box::new(NullGamepadBackend),
         }
     }
 
@@ -44,77 +80,283 @@ impl Window {
         return self.height;
     }
 
-    pub fn init(&mut self, state: &AppState) {
-        let win_size = glutin::dpi::LogicalSize::new(state.width, state.height);
-        let window_builder = glutin::WindowBuilder::new()
-            .with_dimensions(win_size)
-            .with_title(state.title.clone())
-            .with_resizable(false);
-        let context = glutin::ContextBuilder::new().with_vsync(true);
+    /// Physical pixels per logical pixel. Sizes and positions reported by `Window` and drawing
+    /// coordinates are all logical, so they mean the same physical size on any display.
+    pub fn get_dpi_factor(&self) -> f64 {
+        return self.dpi_factor;
+    }
+
+    /// The last known cursor position, with the origin in the bottom left like drawing.
+    pub fn mouse_position(&self) -> Point<f32> {
+        return Point::new(self.mouse_x, self.mouse_y);
+    }
+
+    /// Keyboard and mouse state as of the events handed out so far.
+    pub fn get_input(&self) -> &InputState {
+        return &self.input;
+    }
+
+    /// Replaces where gamepad events come from, for example with a `gamepad::FakeGamepadBackend` in tests.
+    /// Windowed windows start with the platform's backend once initialized, headless ones with none.
+    pub fn set_gamepad_backend(&mut self, backend: Box<dyn GamepadBackend>) {
+        self.gamepads = backend;
+    }
+
+    /// Collects everything that happened since the last frame for `handle_event` to hand out. Called once per
+    /// frame by `App`, so events arriving while the queue is drained wait for the next frame.
+    pub(crate) fn begin_frame(&mut self) {
+        self.input.begin_frame();
+        self.poll_gamepad_events();
+        self.poll_window_events();
+    }
+
+    pub fn is_headless(&self) -> bool {
+        return self.headless_context.is_some();
+    }
+
+    pub fn init(&mut self, state: &AppState) -> SlashResult<()> {
+        match state.backend {
+            Backend::Windowed => {
+                let events_loop = glutin::EventsLoop::new();
+
+                let win_size = glutin::dpi::LogicalSize::new(state.width, state.height);
+                let window_builder = glutin::WindowBuilder::new()
+                    .with_dimensions(win_size)
+                    .with_title(state.title.clone())
+                    .with_resizable(state.resizable);
+                // Stencil bits are needed for clip masks
+                let context = glutin::ContextBuilder::new()
+                    .with_vsync(true)
+                    .with_stencil_buffer(8);
 
-        let events_loop = glutin::EventsLoop::new();
-        let window = glutin::GlWindow::new(window_builder, context, &events_loop).unwrap();
+                let window = glutin::GlWindow::new(window_builder, context, &events_loop)?;
 
-        unsafe { window.make_current().unwrap() };
+                unsafe { window.make_current()? };
 
-        // Load the OpenGL function pointers
-        // TODO: `as *const _` will not be needed once glutin is updated to the latest gl version
-        gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+                // Load the OpenGL function pointers
+                // TODO: `as *const _` will not be needed once glutin is updated to the latest gl version
+                gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+
+                self.dpi_factor = window.get_hidpi_factor();
+                self.window = Some(window);
+                self.events_loop = Some(events_loop);
+                self.gamepads = gamepad::default_backend();
+            }
+            Backend::Headless => {
+                // No events loop either, creating one needs a display server.
+                // The context has no default framebuffer, so Graphics renders into its own framebuffer object.
+                let context = HeadlessContext::new()?;
+
+                gl::load_with(|symbol| context.get_proc_address(symbol));
+
+                self.headless_context = Some(context);
+            }
+        }
 
         self.width = state.width as f32;
         self.height = state.height as f32;
-        self.window = Some(window);
-        self.events_loop = Some(events_loop);
+
+        return Ok(());
     }
 
+    /// Takes the next event collected by the start of this frame.
     pub fn handle_event(&mut self) -> Option<Event> {
+        let event = self.event_queue.pop_front();
+        if let Some(event) = &event {
+            self.input.handle_event(event);
+        }
+
+        return event;
+    }
+
+    fn poll_gamepad_events(&mut self) {
+        let mut gamepad_events = Vec::new();
+        self.gamepads.poll(&mut gamepad_events);
+        self.event_queue.extend(
+            gamepad_events
+                .into_iter()
+                .map(|(id, event)| Event::Gamepad { id, event }),
+        );
+    }
+
+    fn poll_window_events(&mut self) -> Option<()> {
         let Window {
             window,
             event_queue,
             mouse_x,
             mouse_y,
+            width,
             height,
+            dpi_factor,
             events_loop,
             ..
         } = self;
         let window = window.as_mut()?;
 
-        events_loop.as_mut()?.poll_events(|event| match event {
-            glutin::Event::WindowEvent { event, .. } => match event {
-                glutin::WindowEvent::CloseRequested => event_queue.push_back(Event::Close),
-                glutin::WindowEvent::Resized(logical_size) => {
-                    let dpi_factor = window.get_hidpi_factor();
-                    window.resize(logical_size.to_physical(dpi_factor));
-                }
-                glutin::WindowEvent::CursorMoved { position, .. } => {
-                    let (x, y): (f64, f64) = position.into();
-                    *mouse_x = x as f32;
-                    *mouse_y = *height - y as f32;
-                }
-                glutin::WindowEvent::MouseInput { state, .. } => match state {
-                    glutin::ElementState::Released => event_queue.push_back(Event::Click {
-                        position: Point::new(*mouse_x, *mouse_y),
-                    }),
+        events_loop.as_mut()?.poll_events(|event| {
+            if let glutin::Event::WindowEvent { event, .. } = event {
+                match event {
+                    glutin::WindowEvent::CloseRequested => event_queue.push_back(Event::Close),
+                    glutin::WindowEvent::Resized(logical_size) => {
+                        window.resize(logical_size.to_physical(*dpi_factor));
+                        *width = logical_size.width as f32;
+                        *height = logical_size.height as f32;
+                        event_queue.push_back(Event::Resized {
+                            width: *width,
+                            height: *height,
+                        });
+                    }
+                    glutin::WindowEvent::HiDpiFactorChanged(factor) => {
+                        *dpi_factor = factor;
+                        let logical_size =
+                            glutin::dpi::LogicalSize::new(f64::from(*width), f64::from(*height));
+                        window.resize(logical_size.to_physical(factor));
+                        event_queue.push_back(Event::ScaleFactorChanged { factor });
+                    }
+                    glutin::WindowEvent::CursorMoved { position, .. } => {
+                        let (x, y): (f64, f64) = position.into();
+                        *mouse_x = x as f32;
+                        *mouse_y = *height - y as f32;
+                        event_queue.push_back(Event::MouseMove {
+                            position: Point::new(*mouse_x, *mouse_y),
+                        });
+                    }
+                    glutin::WindowEvent::Focused(focused) => {
+                        event_queue.push_back(Event::Focused(focused))
+                    }
+                    glutin::WindowEvent::CursorEntered { .. } => {
+                        event_queue.push_back(Event::CursorEntered)
+                    }
+                    glutin::WindowEvent::CursorLeft { .. } => {
+                        event_queue.push_back(Event::CursorLeft)
+                    }
+                    glutin::WindowEvent::MouseWheel { delta, .. } => {
+                        event_queue.push_back(Event::MouseWheel {
+                            delta: ScrollDelta::from(delta),
+                        })
+                    }
+                    glutin::WindowEvent::KeyboardInput { input, .. } => {
+                        let key = input
+                            .virtual_keycode
+                            .map(Key::from_glutin)
+                            .unwrap_or(Key::Unknown);
+                        let scancode = input.scancode;
+                        let modifiers = Modifiers::from(input.modifiers);
+
+                        event_queue.push_back(match input.state {
+                            glutin::ElementState::Pressed => Event::KeyDown {
+                                key,
+                                scancode,
+                                modifiers,
+                            },
+                            glutin::ElementState::Released => Event::KeyUp {
+                                key,
+                                scancode,
+                                modifiers,
+                            },
+                        });
+                    }
+                    // Control characters like backspace also arrive here, leave those to the key events
+                    glutin::WindowEvent::ReceivedCharacter(c) if !c.is_control() => {
+                        event_queue.push_back(Event::ReceivedCharacter(c))
+                    }
+                    glutin::WindowEvent::MouseInput { state, button, .. } => {
+                        let button = MouseButton::from(button);
+                        match state {
+                            glutin::ElementState::Pressed => {
+                                event_queue.push_back(Event::MouseDown {
+                                    button,
+                                    position: Point::new(*mouse_x, *mouse_y),
+                                })
+                            }
+                            glutin::ElementState::Released => {
+                                event_queue.push_back(Event::MouseUp {
+                                    button,
+                                    position: Point::new(*mouse_x, *mouse_y),
+                                });
+                                event_queue.push_back(Event::Click {
+                                    position: Point::new(*mouse_x, *mouse_y),
+                                });
+                            }
+                        }
+                    }
                     _ => (),
-                },
-                _ => (),
-            },
-            _ => (),
+                }
+            }
         });
 
-        return self.event_queue.pop_front();
+        return Some(());
     }
 
-    pub fn update(&mut self) {
+    pub fn update(&mut self) -> SlashResult<()> {
+        // Headless frames live in an offscreen framebuffer, there is nothing to present.
+        if self.is_headless() {
+            return Ok(());
+        }
+
         self.window
             .as_mut()
-            .expect("No Window")
-            .swap_buffers()
-            .unwrap();
+            .ok_or(SlashError::NoWindow)?
+            .swap_buffers()?;
+
+        return Ok(());
     }
 }
 
+#[derive(Debug)]
 pub enum Event {
     Close,
-    Click { position: Point<f32> },
+    /// The window changed size. `App` has already updated the viewport and projection by the time
+    /// states see this.
+    Resized {
+        width: f32,
+        height: f32,
+    },
+    /// Sent after every `MouseUp`, whatever the button.
+    Click {
+        position: Point<f32>,
+    },
+    MouseDown {
+        button: MouseButton,
+        position: Point<f32>,
+    },
+    MouseUp {
+        button: MouseButton,
+        position: Point<f32>,
+    },
+    MouseMove {
+        position: Point<f32>,
+    },
+    MouseWheel {
+        delta: ScrollDelta,
+    },
+    /// The cursor moved onto the window.
+    CursorEntered,
+    /// The cursor moved off the window.
+    CursorLeft,
+    /// The window moved to a display with a different density. `App` has already adapted the
+    /// viewport, the logical size of the window stays the same.
+    ScaleFactorChanged {
+        factor: f64,
+    },
+    /// A controller connected, disconnected, or changed a button or axis.
+    Gamepad {
+        id: GamepadId,
+        event: GamepadEvent,
+    },
+    /// The window gained or lost keyboard focus. Losing it releases every held key and button.
+    Focused(bool),
+    /// A key was pressed. Holding a key down repeats this event.
+    KeyDown {
+        key: Key,
+        scancode: u32,
+        modifiers: Modifiers,
+    },
+    KeyUp {
+        key: Key,
+        scancode: u32,
+        modifiers: Modifiers,
+    },
+    /// Text input, after the keyboard layout and any input method have been applied.
+    ReceivedCharacter(char),
 }
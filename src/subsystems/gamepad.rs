@@ -0,0 +1,269 @@
+//! Gamepads and joysticks.
+//!
+//! A `GamepadBackend` reports controllers coming and going along with their button and axis changes.
+//! `Window` polls it together with the window's own events, so everything shows up as `Event::Gamepad`,
+//! and `InputState` keeps a `GamepadState` for every connected controller.
+
+#[cfg(target_os = "linux")]
+mod evdev;
+
+#[cfg(target_os = "linux")]
+pub use self::evdev::EvdevBackend;
+
+use std::{
+    cell::RefCell,
+    collections::{
+        HashMap,
+        HashSet,
+        VecDeque,
+    },
+    rc::Rc,
+};
+
+/// Identifies a controller for as long as it stays connected. Ids are not reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GamepadId(pub usize);
+
+/// A button, named by its position on an Xbox style layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    /// A on Xbox, cross on PlayStation.
+    South,
+    /// B on Xbox, circle on PlayStation.
+    East,
+    /// Y on Xbox, triangle on PlayStation.
+    North,
+    /// X on Xbox, square on PlayStation.
+    West,
+    LeftBumper,
+    RightBumper,
+    /// The digital click of the left trigger, on controllers that report one.
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    /// The logo button in the middle.
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    /// A button without a standard meaning, like the ones on flight sticks. Holds the backend's code.
+    Other(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    /// -1 is left, 1 is right.
+    LeftStickX,
+    /// -1 is down, 1 is up.
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    /// 0 is released, 1 is fully pressed.
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvent {
+    Connected { name: String },
+    Disconnected,
+    ButtonDown(GamepadButton),
+    ButtonUp(GamepadButton),
+    AxisMoved { axis: GamepadAxis, value: f32 },
+}
+
+/// Where gamepad events come from.
+pub trait GamepadBackend {
+    /// Appends everything that happened since the last call to `events`. Must not block.
+    fn poll(&mut self, events: &mut Vec<(GamepadId, GamepadEvent)>);
+}
+
+/// A backend without any controllers, for platforms that have no real one yet.
+pub struct NullGamepadBackend;
+
+impl GamepadBackend for NullGamepadBackend {
+    fn poll(&mut self, _events: &mut Vec<(GamepadId, GamepadEvent)>) {}
+}
+
+/// The backend a windowed `Window` starts with on this platform.
+pub fn default_backend() -> Box<dyn GamepadBackend> {
+    #[cfg(target_os = "linux")]
+    return Box::new(EvdevBackend::new());
+
+    #[cfg(not(target_os = "linux"))]
+    return Box::new(NullGamepadBackend);
+}
+
+/// A backend that reports whatever it is told to, for tests and replays.
+///
+/// Clones share their queue, so keep one around after handing another to `Window::set_gamepad_backend`.
+#[derive(Clone, Default)]
+pub struct FakeGamepadBackend {
+    queue: Rc<RefCell<VecDeque<(GamepadId, GamepadEvent)>>>,
+}
+
+impl FakeGamepadBackend {
+    pub fn new() -> Self {
+        return FakeGamepadBackend::default();
+    }
+
+    /// Queues an event to be reported by the next poll.
+    pub fn push(&self, id: GamepadId, event: GamepadEvent) {
+        self.queue.borrow_mut().push_back((id, event));
+    }
+
+    pub fn connect(&self, id: GamepadId, name: &str) {
+        self.push(
+            id,
+            GamepadEvent::Connected {
+                name: name.to_string(),
+            },
+        );
+    }
+
+    pub fn disconnect(&self, id: GamepadId) {
+        self.push(id, GamepadEvent::Disconnected);
+    }
+}
+
+impl GamepadBackend for FakeGamepadBackend {
+    fn poll(&mut self, events: &mut Vec<(GamepadId, GamepadEvent)>) {
+        events.extend(self.queue.borrow_mut().drain(..));
+    }
+}
+
+/// The polled state of one connected controller.
+pub struct GamepadState {
+    name: String,
+    buttons_down: HashSet<GamepadButton>,
+    buttons_pressed: HashSet<GamepadButton>,
+    buttons_released: HashSet<GamepadButton>,
+    axes: HashMap<GamepadAxis, f32>,
+}
+
+impl GamepadState {
+    pub fn new(name: &str) -> Self {
+        GamepadState {
+            name: name.to_string(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+            axes: HashMap::new(),
+        }
+    }
+
+    /// The name the controller reports for itself.
+    pub fn get_name(&self) -> &str {
+        return &self.name;
+    }
+
+    pub fn is_button_down(&self, button: GamepadButton) -> bool {
+        return self.buttons_down.contains(&button);
+    }
+
+    pub fn was_button_pressed(&self, button: GamepadButton) -> bool {
+        return self.buttons_pressed.contains(&button);
+    }
+
+    pub fn was_button_released(&self, button: GamepadButton) -> bool {
+        return self.buttons_released.contains(&button);
+    }
+
+    /// The last reported position of `axis`, or 0 if it never moved or the controller lacks it.
+    pub fn get_axis(&self, axis: GamepadAxis) -> f32 {
+        return self.axes.get(&axis).cloned().unwrap_or(0.0);
+    }
+
+    pub(crate) fn begin_frame(&mut self) {
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &GamepadEvent) {
+        match event {
+            GamepadEvent::ButtonDown(button) => {
+                let newly_down = self.buttons_down.insert(*button);
+                if newly_down {
+                    self.buttons_pressed.insert(*button);
+                }
+            }
+            GamepadEvent::ButtonUp(button) => {
+                let was_down = self.buttons_down.remove(button);
+                if was_down {
+                    self.buttons_released.insert(*button);
+                }
+            }
+            GamepadEvent::AxisMoved { axis, value } => {
+                self.axes.insert(*axis, *value);
+            }
+            GamepadEvent::Connected { .. } | GamepadEvent::Disconnected => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subsystems::{
+        Event,
+        Window,
+    };
+
+    #[test]
+    fn window_surfaces_fake_gamepad_events() {
+        let fake = FakeGamepadBackend::new();
+        let mut window = Window::new();
+        window.set_gamepad_backend(Box::new(fake.clone()));
+
+        let pad = GamepadId(3);
+        fake.connect(pad, "Test Pad");
+        fake.push(pad, GamepadEvent::ButtonDown(GamepadButton::South));
+        fake.push(
+            pad,
+            GamepadEvent::AxisMoved {
+                axis: GamepadAxis::LeftStickX,
+                value: -0.5,
+            },
+        );
+
+        let mut events = Vec::new();
+        window.begin_frame();
+        while let Some(event) = window.handle_event() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 3);
+        match &events[0] {
+            Event::Gamepad {
+                id,
+                event: GamepadEvent::Connected { name },
+            } => {
+                assert_eq!(*id, pad);
+                assert_eq!(name, "Test Pad");
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        let state = window.get_input().get_gamepad(pad).unwrap();
+        assert_eq!(state.get_name(), "Test Pad");
+        assert!(state.is_button_down(GamepadButton::South));
+        assert!(state.was_button_pressed(GamepadButton::South));
+        assert_eq!(state.get_axis(GamepadAxis::LeftStickX), -0.5);
+        assert_eq!(state.get_axis(GamepadAxis::RightTrigger), 0.0);
+
+        // Events arriving mid frame wait for the next one
+        fake.disconnect(pad);
+        assert!(window.handle_event().is_none());
+
+        window.begin_frame();
+        let state = window.get_input().get_gamepad(pad).unwrap();
+        assert!(state.is_button_down(GamepadButton::South));
+        assert!(!state.was_button_pressed(GamepadButton::South));
+
+        while window.handle_event().is_some() {}
+        assert!(window.get_input().get_gamepad(pad).is_none());
+    }
+}
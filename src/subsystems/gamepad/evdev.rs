@@ -0,0 +1,466 @@
+//! Controllers read straight from the Linux event devices in `/dev/input`.
+//! Reading them usually requires the user to be in the `input` group.
+
+use super::{
+    GamepadAxis,
+    GamepadBackend,
+    GamepadButton,
+    GamepadEvent,
+    GamepadId,
+};
+use std::{
+    collections::HashSet,
+    fs::{
+        File,
+        OpenOptions,
+    },
+    io::{
+        ErrorKind,
+        Read,
+    },
+    mem::size_of,
+    os::unix::{
+        fs::OpenOptionsExt,
+        io::AsRawFd,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+const INPUT_DIR: &str = "/dev/input";
+// How often to look for newly plugged in controllers
+const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+
+const KEY_MAX: usize = 0x2ff;
+const ABS_MAX: usize = 0x3f;
+
+const BTN_JOYSTICK: u16 = 0x120;
+const BTN_GAMEPAD: u16 = 0x130;
+
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_Z: u16 = 0x02;
+const ABS_RX: u16 = 0x03;
+const ABS_RY: u16 = 0x04;
+const ABS_RZ: u16 = 0x05;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+// Linux's _IOC(_IOC_READ, 'E', nr, size)
+fn eviocg(nr: usize, size: usize) -> u64 {
+    return (2 << 30) | ((size as u64) << 16) | (u64::from(b'E') << 8) | nr as u64;
+}
+
+fn has_bit(bits: &[u8], bit: usize) -> bool {
+    return bits
+        .get(bit / 8)
+        .is_some_and(|byte| byte & (1 << (bit % 8)) != 0);
+}
+
+/// The numbers of the bits that are set, lowest first.
+fn set_bits(bits: &[u8]) -> Vec<u16> {
+    return (0..bits.len() * 8)
+        .filter(|&bit| has_bit(bits, bit))
+        .map(|bit| bit as u16)
+        .collect();
+}
+
+fn button_from_code(code: u16) -> GamepadButton {
+    match code {
+        0x130 => GamepadButton::South,
+        0x131 => GamepadButton::East,
+        0x133 => GamepadButton::North,
+        0x134 => GamepadButton::West,
+        0x136 => GamepadButton::LeftBumper,
+        0x137 => GamepadButton::RightBumper,
+        0x138 => GamepadButton::LeftTrigger,
+        0x139 => GamepadButton::RightTrigger,
+        0x13a => GamepadButton::Select,
+        0x13b => GamepadButton::Start,
+        0x13c => GamepadButton::Mode,
+        0x13d => GamepadButton::LeftThumb,
+        0x13e => GamepadButton::RightThumb,
+        0x220 => GamepadButton::DPadUp,
+        0x221 => GamepadButton::DPadDown,
+        0x222 => GamepadButton::DPadLeft,
+        0x223 => GamepadButton::DPadRight,
+        code => GamepadButton::Other(code),
+    }
+}
+
+fn axis_from_code(code: u16) -> Option<GamepadAxis> {
+    match code {
+        ABS_X => Some(GamepadAxis::LeftStickX),
+        ABS_Y => Some(GamepadAxis::LeftStickY),
+        ABS_Z => Some(GamepadAxis::LeftTrigger),
+        ABS_RX => Some(GamepadAxis::RightStickX),
+        ABS_RY => Some(GamepadAxis::RightStickY),
+        ABS_RZ => Some(GamepadAxis::RightTrigger),
+        _ => None,
+    }
+}
+
+/// Maps a raw axis value into the range `GamepadAxis` documents.
+fn normalize(axis: GamepadAxis, value: i32, minimum: i32, maximum: i32) -> f32 {
+    if maximum <= minimum {
+        return 0.0;
+    }
+
+    let t = (value - minimum) as f32 / (maximum - minimum) as f32;
+    let value = match axis {
+        GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => t,
+        // evdev sticks point down for positive y
+        GamepadAxis::LeftStickY | GamepadAxis::RightStickY => 1.0 - 2.0 * t,
+        GamepadAxis::LeftStickX | GamepadAxis::RightStickX => 2.0 * t - 1.0,
+    };
+
+    return value.clamp(-1.0, 1.0);
+}
+
+/// Turns a change of a d-pad hat axis into button events.
+fn hat_events(
+    previous: i32,
+    value: i32,
+    negative: GamepadButton,
+    positive: GamepadButton,
+    events: &mut Vec<GamepadEvent>,
+) {
+    let value = value.signum();
+    if previous == value {
+        return;
+    }
+
+    match previous {
+        -1 => events.push(GamepadEvent::ButtonUp(negative)),
+        1 => events.push(GamepadEvent::ButtonUp(positive)),
+        _ => (),
+    }
+
+    match value {
+        -1 => events.push(GamepadEvent::ButtonDown(negative)),
+        1 => events.push(GamepadEvent::ButtonDown(positive)),
+        _ => (),
+    }
+}
+
+struct AxisRange {
+    code: u16,
+    minimum: i32,
+    maximum: i32,
+}
+
+struct Device {
+    id: GamepadId,
+    path: PathBuf,
+    file: File,
+    axes: Vec<AxisRange>,
+    hat: (i32, i32),
+}
+
+impl Device {
+    /// Opens `path` if it is a joystick or gamepad. Also returns its name and events putting the buttons, axes
+    /// and d-pad where they already are, as they only report changes from then on.
+    fn open(path: &Path, id: GamepadId) -> Option<(Self, String, Vec<GamepadEvent>)> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)
+            .ok()?;
+        let fd = file.as_raw_fd();
+
+        let mut key_bits = [0u8; KEY_MAX / 8 + 1];
+        let mut key_state = [0u8; KEY_MAX / 8 + 1];
+        let mut abs_bits = [0u8; ABS_MAX / 8 + 1];
+        let mut name = [0u8; 256];
+
+        unsafe {
+            if libc::ioctl(
+                fd,
+                eviocg(0x20 + EV_KEY as usize, key_bits.len()) as _,
+                key_bits.as_mut_ptr(),
+            ) < 0
+            {
+                return None;
+            }
+            libc::ioctl(
+                fd,
+                eviocg(0x20 + EV_ABS as usize, abs_bits.len()) as _,
+                abs_bits.as_mut_ptr(),
+            );
+            libc::ioctl(fd, eviocg(0x06, name.len()) as _, name.as_mut_ptr());
+            // The buttons held right now
+            libc::ioctl(
+                fd,
+                eviocg(0x18, key_state.len()) as _,
+                key_state.as_mut_ptr(),
+            );
+        }
+
+        if !has_bit(&key_bits, BTN_GAMEPAD as usize) && !has_bit(&key_bits, BTN_JOYSTICK as usize) {
+            return None;
+        }
+
+        let mut axes = Vec::new();
+        let mut values = Vec::new();
+        for &code in &[
+            ABS_X, ABS_Y, ABS_Z, ABS_RX, ABS_RY, ABS_RZ, ABS_HAT0X, ABS_HAT0Y,
+        ] {
+            if !has_bit(&abs_bits, code as usize) {
+                continue;
+            }
+
+            let mut info: libc::input_absinfo = unsafe { std::mem::zeroed() };
+            let request = eviocg(0x40 + code as usize, size_of::<libc::input_absinfo>());
+            if unsafe { libc::ioctl(fd, request as _, &mut info) } < 0 {
+                continue;
+            }
+
+            values.push((code, info.value));
+            if axis_from_code(code).is_some() {
+                axes.push(AxisRange {
+                    code,
+                    minimum: info.minimum,
+                    maximum: info.maximum,
+                });
+            }
+        }
+
+        let name_len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        let name = String::from_utf8_lossy(&name[..name_len]).into_owned();
+
+        let mut device = Device {
+            id,
+            path: path.to_path_buf(),
+            file,
+            axes,
+            hat: (0, 0),
+        };
+
+        let mut events = Vec::new();
+        for code in set_bits(&key_state) {
+            device.translate(EV_KEY, code, 1, &mut events);
+        }
+        for (code, value) in values {
+            device.translate(EV_ABS, code, value, &mut events);
+        }
+
+        return Some((device, name, events));
+    }
+
+    /// Reads every pending event. Returns false once the device is gone.
+    fn read(&mut self, events: &mut Vec<GamepadEvent>) -> bool {
+        const EVENT_SIZE: usize = size_of::<libc::input_event>();
+        let mut buffer = [0u8; EVENT_SIZE * 64];
+
+        loop {
+            let read = match self.file.read(&mut buffer) {
+                Ok(0) => return false,
+                Ok(read) => read,
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => return true,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => return false,
+            };
+
+            for chunk in buffer[..read].chunks_exact(EVENT_SIZE) {
+                let event: libc::input_event =
+                    unsafe { std::ptr::read_unaligned(chunk.as_ptr() as *const _) };
+                self.translate(event.type_, event.code, event.value, events);
+            }
+        }
+    }
+
+    fn translate(&mut self, ty: u16, code: u16, value: i32, events: &mut Vec<GamepadEvent>) {
+        match (ty, code) {
+            // Key repeat, which controllers should not be sending anyway
+            (EV_KEY, _) if value == 2 => (),
+            (EV_KEY, code) if value != 0 => {
+                events.push(GamepadEvent::ButtonDown(button_from_code(code)))
+            }
+            (EV_KEY, code) => events.push(GamepadEvent::ButtonUp(button_from_code(code))),
+            (EV_ABS, ABS_HAT0X) => {
+                hat_events(
+                    self.hat.0,
+                    value,
+                    GamepadButton::DPadLeft,
+                    GamepadButton::DPadRight,
+                    events,
+                );
+                self.hat.0 = value.signum();
+            }
+            // Hats point down for positive y, like the sticks
+            (EV_ABS, ABS_HAT0Y) => {
+                hat_events(
+                    self.hat.1,
+                    value,
+                    GamepadButton::DPadUp,
+                    GamepadButton::DPadDown,
+                    events,
+                );
+                self.hat.1 = value.signum();
+            }
+            (EV_ABS, code) => {
+                let axis = match axis_from_code(code) {
+                    Some(axis) => axis,
+                    None => return,
+                };
+                if let Some(range) = self.axes.iter().find(|range| range.code == code) {
+                    events.push(GamepadEvent::AxisMoved {
+                        axis,
+                        value: normalize(axis, value, range.minimum, range.maximum),
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Finds controllers among the event devices and reads them without blocking.
+pub struct EvdevBackend {
+    devices: Vec<Device>,
+    // Devices that turned out not to be controllers, so they are not probed on every scan
+    ignored: HashSet<PathBuf>,
+    next_id: usize,
+    last_scan: Option<Instant>,
+}
+
+impl EvdevBackend {
+    pub fn new() -> Self {
+        EvdevBackend {
+            devices: Vec::new(),
+            ignored: HashSet::new(),
+            next_id: 0,
+            last_scan: None,
+        }
+    }
+
+    fn scan(&mut self, events: &mut Vec<(GamepadId, GamepadEvent)>) {
+        let entries = match std::fs::read_dir(INPUT_DIR) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("event"))
+            })
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            if self.ignored.contains(&path) || self.devices.iter().any(|d| d.path == path) {
+                continue;
+            }
+
+            match Device::open(&path, GamepadId(self.next_id)) {
+                Some((device, name, initial_events)) => {
+                    self.next_id += 1;
+                    events.push((device.id, GamepadEvent::Connected { name }));
+                    events.extend(initial_events.into_iter().map(|event| (device.id, event)));
+                    self.devices.push(device);
+                }
+                None => {
+                    // Permission errors might go away, only remember devices we could look at
+                    if File::open(&path).is_ok() {
+                        self.ignored.insert(path);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl GamepadBackend for EvdevBackend {
+    // `is_none_or` needs Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    fn poll(&mut self, events: &mut Vec<(GamepadId, GamepadEvent)>) {
+        if self
+            .last_scan
+            .map_or(true, |last| last.elapsed() >= SCAN_INTERVAL)
+        {
+            self.last_scan = Some(Instant::now());
+            self.scan(events);
+        }
+
+        let mut device_events = Vec::new();
+        self.devices.retain_mut(|device| {
+            let connected = device.read(&mut device_events);
+            events.extend(device_events.drain(..).map(|event| (device.id, event)));
+            if !connected {
+                events.push((device.id, GamepadEvent::Disconnected));
+            }
+            return connected;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axes_are_normalized() {
+        let stick = GamepadAxis::LeftStickX;
+        assert_eq!(normalize(stick, -32768, -32768, 32767), -1.0);
+        assert_eq!(normalize(stick, 32767, -32768, 32767), 1.0);
+        assert_eq!(normalize(GamepadAxis::LeftStickY, 0, 0, 255), 1.0);
+        assert_eq!(normalize(GamepadAxis::RightTrigger, 255, 0, 255), 1.0);
+        assert_eq!(normalize(GamepadAxis::RightTrigger, 0, 0, 255), 0.0);
+        assert_eq!(normalize(stick, 5, 0, 0), 0.0);
+    }
+
+    #[test]
+    fn hats_become_dpad_buttons() {
+        let mut events = Vec::new();
+        hat_events(
+            0,
+            -1,
+            GamepadButton::DPadLeft,
+            GamepadButton::DPadRight,
+            &mut events,
+        );
+        hat_events(
+            -1,
+            1,
+            GamepadButton::DPadLeft,
+            GamepadButton::DPadRight,
+            &mut events,
+        );
+        assert_eq!(
+            events,
+            vec![
+                GamepadEvent::ButtonDown(GamepadButton::DPadLeft),
+                GamepadEvent::ButtonUp(GamepadButton::DPadLeft),
+                GamepadEvent::ButtonDown(GamepadButton::DPadRight),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_bits_are_listed_in_order() {
+        assert_eq!(set_bits(&[0b101, 0, 0b1000_0000]), vec![0, 2, 23]);
+        assert!(set_bits(&[0, 0]).is_empty());
+    }
+
+    #[test]
+    fn buttons_map_to_layout_positions() {
+        assert_eq!(button_from_code(0x130), GamepadButton::South);
+        assert_eq!(button_from_code(0x13b), GamepadButton::Start);
+        assert_eq!(button_from_code(0x120), GamepadButton::Other(0x120));
+        assert_eq!(axis_from_code(ABS_RZ), Some(GamepadAxis::RightTrigger));
+        assert_eq!(axis_from_code(ABS_HAT0X), None);
+    }
+}
@@ -0,0 +1,44 @@
+//! GL contexts for `Backend::Headless`, which must work on machines without any display.
+
+#[cfg(target_os = "linux")]
+mod egl;
+
+#[cfg(target_os = "linux")]
+pub use self::egl::HeadlessContext;
+
+#[cfg(not(target_os = "linux"))]
+pub use self::windowless::HeadlessContext;
+
+// Other platforms can make a context without a window through glutin itself
+#[cfg(not(target_os = "linux"))]
+mod windowless {
+    use crate::SlashResult;
+    use glutin::{
+        Context,
+        EventsLoop,
+        GlContext,
+    };
+    use std::os::raw::c_void;
+
+    pub struct HeadlessContext {
+        context: Context,
+        _events_loop: EventsLoop,
+    }
+
+    impl HeadlessContext {
+        pub fn new() -> SlashResult<Self> {
+            let events_loop = EventsLoop::new();
+            let context = Context::new(&events_loop, glutin::ContextBuilder::new(), false)?;
+            unsafe { context.make_current()? };
+
+            return Ok(HeadlessContext {
+                context,
+                _events_loop: events_loop,
+            });
+        }
+
+        pub fn get_proc_address(&self, symbol: &str) -> *const c_void {
+            return self.context.get_proc_address(symbol) as *const _;
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! A GL context made through Mesa's EGL without any surface, so it needs neither X11 nor Wayland.
+//! libEGL is loaded at runtime, machines without it just get an error back.
+
+use crate::{
+    SlashError,
+    SlashResult,
+};
+use std::{
+    ffi::{
+        CStr,
+        CString,
+    },
+    os::raw::{
+        c_char,
+        c_void,
+    },
+    ptr,
+};
+
+type EglBoolean = u32;
+type EglEnum = u32;
+type EglInt = i32;
+type EglDisplay = *mut c_void;
+type EglConfig = *mut c_void;
+type EglContext = *mut c_void;
+type EglSurface = *mut c_void;
+
+const EGL_NONE: EglInt = 0x3038;
+const EGL_RENDERABLE_TYPE: EglInt = 0x3040;
+const EGL_OPENGL_BIT: EglInt = 0x0008;
+const EGL_OPENGL_API: EglEnum = 0x30A2;
+const EGL_EXTENSIONS: EglInt = 0x3055;
+const EGL_CONTEXT_MAJOR_VERSION: EglInt = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: EglInt = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: EglInt = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: EglInt = 0x0001;
+const EGL_PLATFORM_SURFACELESS_MESA: EglEnum = 0x31DD;
+
+type GetProcAddress = unsafe extern "C" fn(*const c_char) -> *const c_void;
+type GetPlatformDisplay = unsafe extern "C" fn(EglEnum, *mut c_void, *const EglInt) -> EglDisplay;
+type Initialize = unsafe extern "C" fn(EglDisplay, *mut EglInt, *mut EglInt) -> EglBoolean;
+type Terminate = unsafe extern "C" fn(EglDisplay) -> EglBoolean;
+type QueryString = unsafe extern "C" fn(EglDisplay, EglInt) -> *const c_char;
+type BindApi = unsafe extern "C" fn(EglEnum) -> EglBoolean;
+type ChooseConfig = unsafe extern "C" fn(
+    EglDisplay,
+    *const EglInt,
+    *mut EglConfig,
+    EglInt,
+    *mut EglInt,
+) -> EglBoolean;
+type CreateContext =
+    unsafe extern "C" fn(EglDisplay, EglConfig, EglContext, *const EglInt) -> EglContext;
+type DestroyContext = unsafe extern "C" fn(EglDisplay, EglContext) -> EglBoolean;
+type MakeCurrent =
+    unsafe extern "C" fn(EglDisplay, EglSurface, EglSurface, EglContext) -> EglBoolean;
+type GetError = unsafe extern "C" fn() -> EglInt;
+
+// libEGL stays loaded once opened: GL objects dropped after the context, like textures kept in a `State`,
+// still call through the function pointers `gl` loaded from it
+struct Egl {
+    get_proc_address: GetProcAddress,
+    initialize: Initialize,
+    terminate: Terminate,
+    query_string: QueryString,
+    bind_api: BindApi,
+    choose_config: ChooseConfig,
+    create_context: CreateContext,
+    destroy_context: DestroyContext,
+    make_current: MakeCurrent,
+    get_error: GetError,
+}
+
+impl Egl {
+    fn load() -> SlashResult<Self> {
+        let library = ["libEGL.so.1", "libEGL.so"]
+            .iter()
+            .map(|name| {
+                let name = CString::new(*name).unwrap();
+                return unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+            })
+            .find(|library| !library.is_null())
+            .ok_or_else(|| error("libEGL could not be loaded"))?;
+
+        let egl = unsafe { Egl::load_functions(library) };
+        if egl.is_err() {
+            unsafe {
+                libc::dlclose(library);
+            }
+        }
+        return egl;
+    }
+
+    unsafe fn load_functions(library: *mut c_void) -> SlashResult<Self> {
+        return Ok(Egl {
+            get_proc_address: function(library, "eglGetProcAddress")?,
+            initialize: function(library, "eglInitialize")?,
+            terminate: function(library, "eglTerminate")?,
+            query_string: function(library, "eglQueryString")?,
+            bind_api: function(library, "eglBindAPI")?,
+            choose_config: function(library, "eglChooseConfig")?,
+            create_context: function(library, "eglCreateContext")?,
+            destroy_context: function(library, "eglDestroyContext")?,
+            make_current: function(library, "eglMakeCurrent")?,
+            get_error: function(library, "eglGetError")?,
+        });
+    }
+
+    // Describes the last EGL error after `what` failed
+    fn failure(&self, what: &str) -> SlashError {
+        let code = unsafe { (self.get_error)() };
+        return error(&format!("{} failed with EGL error 0x{:X}", what, code));
+    }
+}
+
+// Looks up `name` in `library`. `T` must be the function pointer type matching its signature.
+unsafe fn function<T>(library: *mut c_void, name: &str) -> SlashResult<T> {
+    let c_name = CString::new(name).unwrap();
+    let symbol = libc::dlsym(library, c_name.as_ptr());
+    if symbol.is_null() {
+        return Err(error(&format!("libEGL has no {}", name)));
+    }
+    return Ok(std::mem::transmute_copy::<*mut c_void, T>(&symbol));
+}
+
+// Whether `display` supports `extension`, or EGL itself for a null display
+unsafe fn has_extension(query_string: QueryString, display: EglDisplay, extension: &str) -> bool {
+    let extensions = query_string(display, EGL_EXTENSIONS);
+    if extensions.is_null() {
+        return false;
+    }
+    return CStr::from_ptr(extensions)
+        .to_string_lossy()
+        .split(' ')
+        .any(|name| name == extension);
+}
+
+fn error(message: &str) -> SlashError {
+    return SlashError::Headless(message.to_string());
+}
+
+/// An OpenGL 3.3 core context that is current on the thread that made it, with no default framebuffer.
+pub struct HeadlessContext {
+    egl: Egl,
+    display: EglDisplay,
+    context: EglContext,
+}
+
+impl HeadlessContext {
+    /// Creates the context and makes it current.
+    pub fn new() -> SlashResult<Self> {
+        let egl = Egl::load()?;
+
+        unsafe {
+            let has_surfaceless = has_extension(
+                egl.query_string,
+                ptr::null_mut(),
+                "EGL_MESA_platform_surfaceless",
+            );
+            if !has_surfaceless {
+                return Err(error("EGL does not support EGL_MESA_platform_surfaceless"));
+            }
+
+            let name = CString::new("eglGetPlatformDisplayEXT").unwrap();
+            let get_platform_display = (egl.get_proc_address)(name.as_ptr());
+            if get_platform_display.is_null() {
+                return Err(error("EGL has no eglGetPlatformDisplayEXT"));
+            }
+            let get_platform_display =
+                std::mem::transmute::<*const c_void, GetPlatformDisplay>(get_platform_display);
+
+            let display =
+                get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, ptr::null_mut(), ptr::null());
+            if display.is_null() {
+                return Err(egl.failure("eglGetPlatformDisplayEXT"));
+            }
+
+            let (mut major, mut minor) = (0, 0);
+            if (egl.initialize)(display, &mut major, &mut minor) == 0 {
+                return Err(egl.failure("eglInitialize"));
+            }
+
+            // From here on dropping the context terminates the display
+            let mut headless = HeadlessContext {
+                egl,
+                display,
+                context: ptr::null_mut(),
+            };
+            let egl = &headless.egl;
+
+            if (egl.bind_api)(EGL_OPENGL_API) == 0 {
+                return Err(egl.failure("eglBindAPI"));
+            }
+
+            // Without a surface there is nothing for a config to describe, when EGL lets it be left out
+            let config = if has_extension(egl.query_string, display, "EGL_KHR_no_config_context") {
+                ptr::null_mut()
+            } else {
+                let config_attributes = [EGL_RENDERABLE_TYPE, EGL_OPENGL_BIT, EGL_NONE];
+                let mut config = ptr::null_mut();
+                let mut config_count = 0;
+                if (egl.choose_config)(
+                    display,
+                    config_attributes.as_ptr(),
+                    &mut config,
+                    1,
+                    &mut config_count,
+                ) == 0
+                {
+                    return Err(egl.failure("eglChooseConfig"));
+                }
+                if config_count == 0 {
+                    return Err(error("EGL has no config that can render with OpenGL"));
+                }
+                config
+            };
+
+            let context_attributes = [
+                EGL_CONTEXT_MAJOR_VERSION,
+                3,
+                EGL_CONTEXT_MINOR_VERSION,
+                3,
+                EGL_CONTEXT_OPENGL_PROFILE_MASK,
+                EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                EGL_NONE,
+            ];
+            let context = (egl.create_context)(
+                display,
+                config,
+                ptr::null_mut(),
+                context_attributes.as_ptr(),
+            );
+            if context.is_null() {
+                return Err(egl.failure("eglCreateContext"));
+            }
+            headless.context = context;
+
+            let egl = &headless.egl;
+            if (egl.make_current)(display, ptr::null_mut(), ptr::null_mut(), context) == 0 {
+                return Err(egl.failure("eglMakeCurrent"));
+            }
+
+            return Ok(headless);
+        }
+    }
+
+    pub fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        let symbol = match CString::new(symbol) {
+            Ok(symbol) => symbol,
+            Err(_) => return ptr::null(),
+        };
+        return unsafe { (self.egl.get_proc_address)(symbol.as_ptr()) };
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        let egl = &self.egl;
+        unsafe {
+            (egl.make_current)(
+                self.display,
+                ptr::null_mut(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            if !self.context.is_null() {
+                (egl.destroy_context)(self.display, self.context);
+            }
+            (egl.terminate)(self.display);
+        }
+    }
+}
@@ -0,0 +1,690 @@
+use super::{
+    gamepad::{
+        GamepadEvent,
+        GamepadId,
+        GamepadState,
+    },
+    Event,
+};
+use crate::primitives::Point;
+use glutin::VirtualKeyCode;
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+/// A key on the keyboard, independent of the windowing library.
+/// Letters and digits follow the key's label on the user's layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+
+    Escape,
+    Tab,
+    Backspace,
+    Enter,
+    Space,
+
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+
+    Left,
+    Right,
+    Up,
+    Down,
+
+    LShift,
+    RShift,
+    LControl,
+    RControl,
+    LAlt,
+    RAlt,
+    /// The left "windows" or "command" key.
+    LSuper,
+    /// The right "windows" or "command" key.
+    RSuper,
+    Menu,
+
+    CapsLock,
+    NumLock,
+    ScrollLock,
+    PrintScreen,
+    Pause,
+
+    Apostrophe,
+    Backslash,
+    Comma,
+    Equals,
+    Grave,
+    LBracket,
+    RBracket,
+    Minus,
+    Period,
+    Semicolon,
+    Slash,
+
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadComma,
+    NumpadEnter,
+    NumpadEquals,
+
+    /// A key with no mapping. The `scancode` of the key event still identifies it.
+    Unknown,
+}
+
+impl Key {
+    pub(crate) fn from_glutin(code: VirtualKeyCode) -> Self {
+        match code {
+            VirtualKeyCode::A => Key::A,
+            VirtualKeyCode::B => Key::B,
+            VirtualKeyCode::C => Key::C,
+            VirtualKeyCode::D => Key::D,
+            VirtualKeyCode::E => Key::E,
+            VirtualKeyCode::F => Key::F,
+            VirtualKeyCode::G => Key::G,
+            VirtualKeyCode::H => Key::H,
+            VirtualKeyCode::I => Key::I,
+            VirtualKeyCode::J => Key::J,
+            VirtualKeyCode::K => Key::K,
+            VirtualKeyCode::L => Key::L,
+            VirtualKeyCode::M => Key::M,
+            VirtualKeyCode::N => Key::N,
+            VirtualKeyCode::O => Key::O,
+            VirtualKeyCode::P => Key::P,
+            VirtualKeyCode::Q => Key::Q,
+            VirtualKeyCode::R => Key::R,
+            VirtualKeyCode::S => Key::S,
+            VirtualKeyCode::T => Key::T,
+            VirtualKeyCode::U => Key::U,
+            VirtualKeyCode::V => Key::V,
+            VirtualKeyCode::W => Key::W,
+            VirtualKeyCode::X => Key::X,
+            VirtualKeyCode::Y => Key::Y,
+            VirtualKeyCode::Z => Key::Z,
+
+            VirtualKeyCode::Key0 => Key::Num0,
+            VirtualKeyCode::Key1 => Key::Num1,
+            VirtualKeyCode::Key2 => Key::Num2,
+            VirtualKeyCode::Key3 => Key::Num3,
+            VirtualKeyCode::Key4 => Key::Num4,
+            VirtualKeyCode::Key5 => Key::Num5,
+            VirtualKeyCode::Key6 => Key::Num6,
+            VirtualKeyCode::Key7 => Key::Num7,
+            VirtualKeyCode::Key8 => Key::Num8,
+            VirtualKeyCode::Key9 => Key::Num9,
+
+            VirtualKeyCode::F1 => Key::F1,
+            VirtualKeyCode::F2 => Key::F2,
+            VirtualKeyCode::F3 => Key::F3,
+            VirtualKeyCode::F4 => Key::F4,
+            VirtualKeyCode::F5 => Key::F5,
+            VirtualKeyCode::F6 => Key::F6,
+            VirtualKeyCode::F7 => Key::F7,
+            VirtualKeyCode::F8 => Key::F8,
+            VirtualKeyCode::F9 => Key::F9,
+            VirtualKeyCode::F10 => Key::F10,
+            VirtualKeyCode::F11 => Key::F11,
+            VirtualKeyCode::F12 => Key::F12,
+            VirtualKeyCode::F13 => Key::F13,
+            VirtualKeyCode::F14 => Key::F14,
+            VirtualKeyCode::F15 => Key::F15,
+            VirtualKeyCode::F16 => Key::F16,
+            VirtualKeyCode::F17 => Key::F17,
+            VirtualKeyCode::F18 => Key::F18,
+            VirtualKeyCode::F19 => Key::F19,
+            VirtualKeyCode::F20 => Key::F20,
+            VirtualKeyCode::F21 => Key::F21,
+            VirtualKeyCode::F22 => Key::F22,
+            VirtualKeyCode::F23 => Key::F23,
+            VirtualKeyCode::F24 => Key::F24,
+
+            VirtualKeyCode::Escape => Key::Escape,
+            VirtualKeyCode::Tab => Key::Tab,
+            VirtualKeyCode::Back => Key::Backspace,
+            VirtualKeyCode::Return => Key::Enter,
+            VirtualKeyCode::Space => Key::Space,
+
+            VirtualKeyCode::Insert => Key::Insert,
+            VirtualKeyCode::Delete => Key::Delete,
+            VirtualKeyCode::Home => Key::Home,
+            VirtualKeyCode::End => Key::End,
+            VirtualKeyCode::PageUp => Key::PageUp,
+            VirtualKeyCode::PageDown => Key::PageDown,
+
+            VirtualKeyCode::Left => Key::Left,
+            VirtualKeyCode::Right => Key::Right,
+            VirtualKeyCode::Up => Key::Up,
+            VirtualKeyCode::Down => Key::Down,
+
+            VirtualKeyCode::LShift => Key::LShift,
+            VirtualKeyCode::RShift => Key::RShift,
+            VirtualKeyCode::LControl => Key::LControl,
+            VirtualKeyCode::RControl => Key::RControl,
+            VirtualKeyCode::LAlt => Key::LAlt,
+            VirtualKeyCode::RAlt => Key::RAlt,
+            VirtualKeyCode::LWin => Key::LSuper,
+            VirtualKeyCode::RWin => Key::RSuper,
+            VirtualKeyCode::Apps => Key::Menu,
+
+            VirtualKeyCode::Capital => Key::CapsLock,
+            VirtualKeyCode::Numlock => Key::NumLock,
+            VirtualKeyCode::Scroll => Key::ScrollLock,
+            VirtualKeyCode::Snapshot | VirtualKeyCode::Sysrq => Key::PrintScreen,
+            VirtualKeyCode::Pause => Key::Pause,
+
+            VirtualKeyCode::Apostrophe => Key::Apostrophe,
+            VirtualKeyCode::Backslash => Key::Backslash,
+            VirtualKeyCode::Comma => Key::Comma,
+            VirtualKeyCode::Equals => Key::Equals,
+            VirtualKeyCode::Grave => Key::Grave,
+            VirtualKeyCode::LBracket => Key::LBracket,
+            VirtualKeyCode::RBracket => Key::RBracket,
+            VirtualKeyCode::Minus => Key::Minus,
+            VirtualKeyCode::Period => Key::Period,
+            VirtualKeyCode::Semicolon => Key::Semicolon,
+            VirtualKeyCode::Slash => Key::Slash,
+
+            VirtualKeyCode::Numpad0 => Key::Numpad0,
+            VirtualKeyCode::Numpad1 => Key::Numpad1,
+            VirtualKeyCode::Numpad2 => Key::Numpad2,
+            VirtualKeyCode::Numpad3 => Key::Numpad3,
+            VirtualKeyCode::Numpad4 => Key::Numpad4,
+            VirtualKeyCode::Numpad5 => Key::Numpad5,
+            VirtualKeyCode::Numpad6 => Key::Numpad6,
+            VirtualKeyCode::Numpad7 => Key::Numpad7,
+            VirtualKeyCode::Numpad8 => Key::Numpad8,
+            VirtualKeyCode::Numpad9 => Key::Numpad9,
+            VirtualKeyCode::Add => Key::NumpadAdd,
+            VirtualKeyCode::Subtract => Key::NumpadSubtract,
+            VirtualKeyCode::Multiply => Key::NumpadMultiply,
+            VirtualKeyCode::Divide => Key::NumpadDivide,
+            VirtualKeyCode::Decimal => Key::NumpadDecimal,
+            VirtualKeyCode::NumpadComma => Key::NumpadComma,
+            VirtualKeyCode::NumpadEnter => Key::NumpadEnter,
+            VirtualKeyCode::NumpadEquals => Key::NumpadEquals,
+
+            _ => Key::Unknown,
+        }
+    }
+}
+
+/// The modifier keys held down when a key event happened.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// The "windows" or "command" key.
+    pub logo: bool,
+}
+
+impl From<glutin::ModifiersState> for Modifiers {
+    fn from(state: glutin::ModifiersState) -> Self {
+        Modifiers {
+            shift: state.shift,
+            ctrl: state.ctrl,
+            alt: state.alt,
+            logo: state.logo,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u8),
+}
+
+impl From<glutin::MouseButton> for MouseButton {
+    fn from(button: glutin::MouseButton) -> Self {
+        match button {
+            glutin::MouseButton::Left => MouseButton::Left,
+            glutin::MouseButton::Right => MouseButton::Right,
+            glutin::MouseButton::Middle => MouseButton::Middle,
+            glutin::MouseButton::Other(n) => MouseButton::Other(n),
+        }
+    }
+}
+
+/// How far the mouse wheel or touchpad scrolled. Positive `y` scrolls up and positive `x` scrolls right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollDelta {
+    /// Mouse wheels report whole lines or rows.
+    Lines { x: f32, y: f32 },
+    /// Touchpads report the exact distance in pixels.
+    Pixels { x: f32, y: f32 },
+}
+
+impl From<glutin::MouseScrollDelta> for ScrollDelta {
+    fn from(delta: glutin::MouseScrollDelta) -> Self {
+        match delta {
+            glutin::MouseScrollDelta::LineDelta(x, y) => ScrollDelta::Lines { x, y },
+            glutin::MouseScrollDelta::PixelDelta(position) => ScrollDelta::Pixels {
+                x: position.x as f32,
+                y: position.y as f32,
+            },
+        }
+    }
+}
+
+// Where a held key is tracked. Unmapped keys go by scancode so they do not share `Key::Unknown`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum KeySlot {
+    Key(Key),
+    Scancode(u32),
+}
+
+impl KeySlot {
+    fn new(key: Key, scancode: u32) -> Self {
+        match key {
+            Key::Unknown => KeySlot::Scancode(scancode),
+            key => KeySlot::Key(key),
+        }
+    }
+}
+
+/// A snapshot of the keyboard and mouse, for code that would rather ask than handle every `Event`.
+///
+/// `Window` feeds it every event it hands out. The "this frame" queries cover the events drained since
+/// `App::main_loop` last called `begin_frame`.
+pub struct InputState {
+    keys_down: HashSet<KeySlot>,
+    keys_pressed: HashSet<KeySlot>,
+    keys_released: HashSet<KeySlot>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    mouse_x: f32,
+    mouse_y: f32,
+    scroll_lines: (f32, f32),
+    scroll_pixels: (f32, f32),
+    text: String,
+    gamepads: HashMap<GamepadId, GamepadState>,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        InputState {
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+            mouse_x: 0.0,
+            mouse_y: 0.0,
+            scroll_lines: (0.0, 0.0),
+            scroll_pixels: (0.0, 0.0),
+            text: String::new(),
+            gamepads: HashMap::new(),
+        }
+    }
+
+    /// Whether `key` is held. Always false for `Key::Unknown`, see `is_unknown_key_down`.
+    pub fn is_key_down(&self, key: Key) -> bool {
+        return self.keys_down.contains(&KeySlot::Key(key));
+    }
+
+    /// Whether `key` went down this frame. Key repeat does not count.
+    pub fn was_key_pressed(&self, key: Key) -> bool {
+        return self.keys_pressed.contains(&KeySlot::Key(key));
+    }
+
+    pub fn was_key_released(&self, key: Key) -> bool {
+        return self.keys_released.contains(&KeySlot::Key(key));
+    }
+
+    /// Whether the key with `scancode` is held, for keys that map to `Key::Unknown`. Keys with a `Key` are only
+    /// tracked by it.
+    pub fn is_unknown_key_down(&self, scancode: u32) -> bool {
+        return self.keys_down.contains(&KeySlot::Scancode(scancode));
+    }
+
+    pub fn was_unknown_key_pressed(&self, scancode: u32) -> bool {
+        return self.keys_pressed.contains(&KeySlot::Scancode(scancode));
+    }
+
+    pub fn was_unknown_key_released(&self, scancode: u32) -> bool {
+        return self.keys_released.contains(&KeySlot::Scancode(scancode));
+    }
+
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        return self.buttons_down.contains(&button);
+    }
+
+    pub fn was_mouse_button_pressed(&self, button: MouseButton) -> bool {
+        return self.buttons_pressed.contains(&button);
+    }
+
+    pub fn was_mouse_button_released(&self, button: MouseButton) -> bool {
+        return self.buttons_released.contains(&button);
+    }
+
+    /// The last known cursor position, with the origin in the bottom left like drawing.
+    pub fn mouse_position(&self) -> Point<f32> {
+        return Point::new(self.mouse_x, self.mouse_y);
+    }
+
+    /// Wheel scrolling this frame, in lines. See `ScrollDelta` for the direction.
+    pub fn get_scroll_lines(&self) -> Point<f32> {
+        return Point::new(self.scroll_lines.0, self.scroll_lines.1);
+    }
+
+    /// Touchpad scrolling this frame, in pixels.
+    pub fn get_scroll_pixels(&self) -> Point<f32> {
+        return Point::new(self.scroll_pixels.0, self.scroll_pixels.1);
+    }
+
+    /// Text typed this frame.
+    pub fn get_text(&self) -> &str {
+        return &self.text;
+    }
+
+    /// The state of a connected controller, `None` once it disconnects.
+    pub fn get_gamepad(&self, id: GamepadId) -> Option<&GamepadState> {
+        return self.gamepads.get(&id);
+    }
+
+    /// Every connected controller, in the order they were connected.
+    pub fn get_gamepads(&self) -> Vec<(GamepadId, &GamepadState)> {
+        let mut gamepads: Vec<_> = self
+            .gamepads
+            .iter()
+            .map(|(id, state)| (*id, state))
+            .collect();
+        gamepads.sort_by_key(|(id, _)| *id);
+        return gamepads;
+    }
+
+    /// Forgets everything that only applies to the previous frame. Held keys and buttons stay held.
+    pub fn begin_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.scroll_lines = (0.0, 0.0);
+        self.scroll_pixels = (0.0, 0.0);
+        self.text.clear();
+        for gamepad in self.gamepads.values_mut() {
+            gamepad.begin_frame();
+        }
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &Event) {
+        match event {
+            Event::KeyDown { key, scancode, .. } => {
+                let slot = KeySlot::new(*key, *scancode);
+                let newly_down = self.keys_down.insert(slot);
+                if newly_down {
+                    self.keys_pressed.insert(slot);
+                }
+            }
+            Event::KeyUp { key, scancode, .. } => {
+                let slot = KeySlot::new(*key, *scancode);
+                let was_down = self.keys_down.remove(&slot);
+                if was_down {
+                    self.keys_released.insert(slot);
+                }
+            }
+            Event::MouseDown { button, position } => {
+                self.set_mouse_position(position);
+                if self.buttons_down.insert(*button) {
+                    self.buttons_pressed.insert(*button);
+                }
+            }
+            Event::MouseUp { button, position } => {
+                self.set_mouse_position(position);
+                if self.buttons_down.remove(button) {
+                    self.buttons_released.insert(*button);
+                }
+            }
+            Event::MouseMove { position } => self.set_mouse_position(position),
+            Event::MouseWheel { delta } => match *delta {
+                ScrollDelta::Lines { x, y } => {
+                    self.scroll_lines.0 += x;
+                    self.scroll_lines.1 += y;
+                }
+                ScrollDelta::Pixels { x, y } => {
+                    self.scroll_pixels.0 += x;
+                    self.scroll_pixels.1 += y;
+                }
+            },
+            Event::ReceivedCharacter(c) => self.text.push(*c),
+            Event::Gamepad { id, event } => match event {
+                GamepadEvent::Connected { name } => {
+                    self.gamepads.insert(*id, GamepadState::new(name));
+                }
+                GamepadEvent::Disconnected => {
+                    self.gamepads.remove(id);
+                }
+                event => {
+                    if let Some(gamepad) = self.gamepads.get_mut(id) {
+                        gamepad.handle_event(event);
+                    }
+                }
+            },
+            // Releases that happen while another window has focus never reach us
+            Event::Focused(false) => {
+                self.keys_released.extend(self.keys_down.drain());
+                self.buttons_released.extend(self.buttons_down.drain());
+            }
+            _ => (),
+        }
+    }
+
+    fn set_mouse_position(&mut self, position: &Point<f32>) {
+        self.mouse_x = position.x;
+        self.mouse_y = position.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_map_from_glutin() {
+        assert_eq!(Key::from_glutin(VirtualKeyCode::Q), Key::Q);
+        assert_eq!(Key::from_glutin(VirtualKeyCode::Key7), Key::Num7);
+        assert_eq!(Key::from_glutin(VirtualKeyCode::Back), Key::Backspace);
+        assert_eq!(Key::from_glutin(VirtualKeyCode::Return), Key::Enter);
+        assert_eq!(Key::from_glutin(VirtualKeyCode::LWin), Key::LSuper);
+        assert_eq!(Key::from_glutin(VirtualKeyCode::Mail), Key::Unknown);
+    }
+
+    #[test]
+    fn mouse_input_maps_from_glutin() {
+        assert_eq!(
+            MouseButton::from(glutin::MouseButton::Other(4)),
+            MouseButton::Other(4)
+        );
+        assert_eq!(
+            ScrollDelta::from(glutin::MouseScrollDelta::LineDelta(0.0, -2.0)),
+            ScrollDelta::Lines { x: 0.0, y: -2.0 }
+        );
+        assert_eq!(
+            ScrollDelta::from(glutin::MouseScrollDelta::PixelDelta(
+                glutin::dpi::LogicalPosition::new(3.0, 4.5)
+            )),
+            ScrollDelta::Pixels { x: 3.0, y: 4.5 }
+        );
+    }
+
+    fn key_down(key: Key) -> Event {
+        return Event::KeyDown {
+            key,
+            scancode: 0,
+            modifiers: Modifiers::default(),
+        };
+    }
+
+    #[test]
+    fn input_state_tracks_presses_per_frame() {
+        let mut input = InputState::new();
+        input.handle_event(&key_down(Key::Space));
+        input.handle_event(&key_down(Key::Space));
+        input.handle_event(&Event::MouseDown {
+            button: MouseButton::Left,
+            position: Point::new(4.0, 5.0),
+        });
+        input.handle_event(&Event::ReceivedCharacter('a'));
+
+        assert!(input.is_key_down(Key::Space));
+        assert!(input.was_key_pressed(Key::Space));
+        assert!(input.was_mouse_button_pressed(MouseButton::Left));
+        assert_eq!(input.mouse_position().x, 4.0);
+        assert_eq!(input.get_text(), "a");
+
+        input.begin_frame();
+        // A repeated KeyDown is not a new press
+        input.handle_event(&key_down(Key::Space));
+
+        assert!(input.is_key_down(Key::Space));
+        assert!(!input.was_key_pressed(Key::Space));
+        assert!(input.is_mouse_button_down(MouseButton::Left));
+        assert!(!input.was_mouse_button_pressed(MouseButton::Left));
+        assert_eq!(input.get_text(), "");
+
+        input.handle_event(&Event::KeyUp {
+            key: Key::Space,
+            scancode: 0,
+            modifiers: Modifiers::default(),
+        });
+        assert!(!input.is_key_down(Key::Space));
+        assert!(input.was_key_released(Key::Space));
+    }
+
+    #[test]
+    fn unknown_keys_are_tracked_by_scancode() {
+        let mut input = InputState::new();
+        for scancode in [100, 101] {
+            input.handle_event(&Event::KeyDown {
+                key: Key::Unknown,
+                scancode,
+                modifiers: Modifiers::default(),
+            });
+        }
+        input.handle_event(&Event::KeyUp {
+            key: Key::Unknown,
+            scancode: 100,
+            modifiers: Modifiers::default(),
+        });
+
+        assert!(!input.is_unknown_key_down(100));
+        assert!(input.was_unknown_key_released(100));
+        assert!(input.is_unknown_key_down(101));
+        assert!(input.was_unknown_key_pressed(101));
+        assert!(!input.is_key_down(Key::Unknown));
+    }
+
+    #[test]
+    fn losing_focus_releases_everything() {
+        let mut input = InputState::new();
+        input.handle_event(&key_down(Key::W));
+        input.handle_event(&Event::Focused(false));
+
+        assert!(!input.is_key_down(Key::W));
+        assert!(input.was_key_released(Key::W));
+    }
+
+    #[test]
+    fn modifiers_map_from_glutin() {
+        let state = glutin::ModifiersState {
+            shift: true,
+            ctrl: false,
+            alt: true,
+            logo: false,
+        };
+
+        assert_eq!(
+            Modifiers::from(state),
+            Modifiers {
+                shift: true,
+                ctrl: false,
+                alt: true,
+                logo: false,
+            }
+        );
+    }
+}
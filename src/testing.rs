@@ -0,0 +1,241 @@
+//! Golden image testing for anything drawn through `Graphics`.
+//!
+//! A test renders a `State` for a few frames with the headless backend, captures the result
+//! and compares it against a PNG stored on disk. Missing or outdated goldens can be (re)written by
+//! running the tests with `SLASH_BLESS=1`. On a mismatch the captured frame and a diff image are
+//! written next to the golden as `<name>.actual.png` and `<name>.diff.png`.
+
+use crate::{
+    App,
+    AppState,
+    Backend,
+    SlashResult,
+    State,
+};
+use image::{
+    Rgba,
+    RgbaImage,
+};
+use std::{
+    path::PathBuf,
+    sync::Mutex,
+};
+
+/// Set this environment variable to write captured frames as the new goldens.
+pub const BLESS_VAR: &str = "SLASH_BLESS";
+
+// Creating several GL contexts at once from the test threads is not something every driver enjoys.
+static RENDER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Runs `state` in a headless app for `frames` frames and captures the last one.
+pub fn render_frames<S: State>(
+    state: S,
+    width: u32,
+    height: u32,
+    frames: usize,
+) -> SlashResult<RgbaImage> {
+    let _guard = RENDER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut app_state = AppState::new();
+    app_state.width = f64::from(width);
+    app_state.height = f64::from(height);
+    app_state.backend = Backend::Headless;
+
+    let mut app = App::new();
+    app.init_app_state(app_state);
+    app.set_state(state);
+    app.init()?;
+
+    for _ in 0..frames.max(1) {
+        app.main_loop()?;
+    }
+
+    return Ok(app.graphics.capture());
+}
+
+/// The result of comparing two images pixel by pixel.
+pub struct Comparison {
+    /// Number of pixels where any channel differs by more than the tolerance.
+    pub mismatched: usize,
+    /// Largest channel difference found anywhere in the image.
+    pub max_difference: u8,
+    /// Mismatched pixels in red over a faded copy of the expected image.
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    pub fn is_match(&self) -> bool {
+        return self.mismatched == 0;
+    }
+}
+
+/// Compares two images, allowing every channel of every pixel to be off by up to `tolerance`.
+/// Returns `None` if the images are not the same size.
+pub fn compare_images(
+    actual: &RgbaImage,
+    expected: &RgbaImage,
+    tolerance: u8,
+) -> Option<Comparison> {
+    if actual.dimensions() != expected.dimensions() {
+        return None;
+    }
+
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+
+    for ((a, e), d) in actual
+        .pixels()
+        .zip(expected.pixels())
+        .zip(diff.pixels_mut())
+    {
+        let difference = a
+            .data
+            .iter()
+            .zip(e.data.iter())
+            .map(|(a, e)| (i16::from(*a) - i16::from(*e)).unsigned_abs() as u8)
+            .max()
+            .unwrap_or(0);
+
+        max_difference = max_difference.max(difference);
+
+        if difference > tolerance {
+            mismatched += 1;
+            *d = Rgba([255, 0, 0, 255]);
+        } else {
+            let luma = (u16::from(e.data[0]) + u16::from(e.data[1]) + u16::from(e.data[2])) / 3;
+            let faded = (luma / 4) as u8;
+            *d = Rgba([faded, faded, faded, 255]);
+        }
+    }
+
+    return Some(Comparison {
+        mismatched,
+        max_difference,
+        diff,
+    });
+}
+
+/// A directory of golden images and the tolerance to compare against them with.
+pub struct Golden {
+    dir: PathBuf,
+    tolerance: u8,
+}
+
+impl Golden {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Golden {
+            dir: dir.into(),
+            tolerance: 0,
+        }
+    }
+
+    /// Sets the allowed per-channel difference for each pixel.
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Compares `actual` against `<dir>/<name>.png`, writing debug images on failure.
+    pub fn check(&self, name: &str, actual: &RgbaImage) -> Result<(), String> {
+        let golden_path = self.dir.join(format!("{}.png", name));
+        let actual_path = self.dir.join(format!("{}.actual.png", name));
+        let diff_path = self.dir.join(format!("{}.diff.png", name));
+
+        if std::env::var_os(BLESS_VAR).is_some() {
+            std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+            actual.save(&golden_path).map_err(|e| e.to_string())?;
+            let _ = std::fs::remove_file(&actual_path);
+            let _ = std::fs::remove_file(&diff_path);
+            return Ok(());
+        }
+
+        let expected = match image::open(&golden_path) {
+            Ok(img) => img.to_rgba(),
+            Err(e) => {
+                return Err(format!(
+                    "Could not load golden '{}' ({}). Run with {}=1 to create it.",
+                    golden_path.display(),
+                    e,
+                    BLESS_VAR
+                ));
+            }
+        };
+
+        let comparison = match compare_images(actual, &expected, self.tolerance) {
+            Some(comparison) => comparison,
+            None => {
+                let _ = actual.save(&actual_path);
+                return Err(format!(
+                    "'{}' is {:?} but the golden is {:?}",
+                    name,
+                    actual.dimensions(),
+                    expected.dimensions()
+                ));
+            }
+        };
+
+        if comparison.is_match() {
+            let _ = std::fs::remove_file(&actual_path);
+            let _ = std::fs::remove_file(&diff_path);
+            return Ok(());
+        }
+
+        let _ = actual.save(&actual_path);
+        let _ = comparison.diff.save(&diff_path);
+
+        return Err(format!(
+            "'{}' differs from its golden in {} pixels (max difference {}, tolerance {}). See '{}'.",
+            name,
+            comparison.mismatched,
+            comparison.max_difference,
+            self.tolerance,
+            diff_path.display()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        return RgbaImage::from_pixel(width, height, Rgba(color));
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let a = solid(4, 4, [10, 20, 30, 255]);
+        let comparison = compare_images(&a, &a.clone(), 0).unwrap();
+        assert!(comparison.is_match());
+        assert_eq!(comparison.max_difference, 0);
+    }
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let a = solid(4, 4, [10, 20, 30, 255]);
+        let b = solid(4, 4, [12, 18, 30, 255]);
+        assert!(compare_images(&a, &b, 2).unwrap().is_match());
+        assert!(!compare_images(&a, &b, 1).unwrap().is_match());
+    }
+
+    #[test]
+    fn mismatched_pixels_are_counted_and_marked() {
+        let a = solid(3, 3, [0, 0, 0, 255]);
+        let mut b = a.clone();
+        b.put_pixel(1, 2, Rgba([255, 255, 255, 255]));
+
+        let comparison = compare_images(&a, &b, 8).unwrap();
+        assert_eq!(comparison.mismatched, 1);
+        assert_eq!(comparison.max_difference, 255);
+        assert_eq!(comparison.diff.get_pixel(1, 2), &Rgba([255, 0, 0, 255]));
+        assert_eq!(comparison.diff.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn different_sizes_do_not_compare() {
+        let a = solid(3, 3, [0, 0, 0, 255]);
+        let b = solid(3, 4, [0, 0, 0, 255]);
+        assert!(compare_images(&a, &b, 255).is_none());
+    }
+}
@@ -0,0 +1,851 @@
+#![allow(clippy::needless_return)]
+
+extern crate image;
+extern crate slash;
+
+use slash::{
+    graphics::{
+        Camera2D,
+        Color,
+        CornerRadii,
+        FontId,
+        Graphics,
+        LayoutOptions,
+        LineCap,
+        LineJoin,
+        Paint,
+        Path,
+        RichText,
+        ShapeStyle,
+        SpriteRenderer,
+        StrokeStyle,
+        TextAlign,
+        TextEffects,
+        TextMode,
+        TextSpan,
+        Texture,
+        Transform,
+    },
+    primitives::{
+        Point,
+        Rect,
+    },
+    resources::ResourceManager,
+    subsystems::Window,
+    testing::{
+        self,
+        Golden,
+    },
+    AppState,
+    State,
+};
+use std::{
+    path::PathBuf,
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+const FRAMES: usize = 2;
+
+fn golden() -> Golden {
+    return Golden::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden")).tolerance(2);
+}
+
+fn check<S: State>(name: &str) {
+    let image = match testing::render_frames(S::new(), WIDTH, HEIGHT, FRAMES) {
+        Ok(image) => image,
+        Err(e) => panic!("Failed to render '{}': {}", name, e),
+    };
+    if let Err(e) = golden().check(name, &image) {
+        panic!("{}", e);
+    }
+}
+
+struct RectState;
+
+impl State for RectState {
+    fn new() -> Self {
+        RectState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        renderer.draw_rect(
+            &Rect::new(10.0, 10.0, 60.0, 40.0),
+            &Color::from_rgba(255, 0, 0, 255),
+        );
+        renderer.draw_rect(
+            &Rect::new(50.0, 30.0, 80.0, 70.0),
+            &Color::from_rgba(0, 0, 255, 128),
+        );
+    }
+}
+
+struct CircleState;
+
+impl State for CircleState {
+    fn new() -> Self {
+        CircleState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        renderer.draw_rect(
+            &Rect::new(0.0, 0.0, WIDTH as f32, HEIGHT as f32),
+            &Color::from_rgba(255, 255, 255, 255),
+        );
+        renderer.draw_circle(
+            &Point::new(80.0, 60.0),
+            40.0,
+            &ShapeStyle::new()
+                .fill(Color::from_rgba(255, 0, 255, 255))
+                .stroke(Color::from_rgba(0, 0, 0, 255), 6.0),
+        );
+        renderer.draw_ellipse(
+            &Point::new(30.0, 30.0),
+            20.0,
+            10.0,
+            &ShapeStyle::new().fill(Color::from_rgba(0, 120, 255, 255)),
+        );
+        renderer.draw_ellipse(
+            &Point::new(135.0, 100.0),
+            20.0,
+            6.0,
+            &ShapeStyle::new().stroke(Color::from_rgba(0, 160, 0, 255), 1.5),
+        );
+        renderer.draw_circle(
+            &Point::new(140.0, 20.0),
+            1.5,
+            &ShapeStyle::new().fill(Color::from_rgba(0, 0, 0, 255)),
+        );
+        // Shapes without an area draw nothing
+        let zero_style = ShapeStyle::new()
+            .fill(Color::from_rgba(255, 0, 0, 255))
+            .stroke(Color::from_rgba(0, 0, 255, 255), 2.0);
+        renderer.draw_circle(&Point::new(20.0, 100.0), 0.0, &zero_style);
+        renderer.draw_ellipse(&Point::new(40.0, 100.0), 0.0, 8.0, &zero_style);
+        renderer.draw_ellipse(&Point::new(60.0, 100.0), 8.0, 0.0, &zero_style);
+    }
+}
+
+struct LineState;
+
+impl State for LineState {
+    fn new() -> Self {
+        LineState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        renderer.draw_line(
+            10.0,
+            10.0,
+            150.0,
+            110.0,
+            4.0,
+            &Color::from_rgba(0, 255, 0, 255),
+        );
+        renderer.draw_line(
+            10.0,
+            110.0,
+            150.0,
+            10.0,
+            1.0,
+            &Color::from_rgba(255, 255, 0, 255),
+        );
+        renderer.draw_line(
+            80.0,
+            5.0,
+            80.0,
+            115.0,
+            10.0,
+            &Color::from_rgba(255, 0, 255, 200),
+        );
+    }
+}
+
+struct TextState;
+
+impl State for TextState {
+    fn new() -> Self {
+        TextState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        renderer.draw_text(
+            renderer.default_font(),
+            &Point::new(10.0, 40.0),
+            "Slash",
+            32.0,
+            &Color::from_rgba(255, 255, 255, 255),
+        );
+    }
+}
+
+/// Text and a rect drawn at the same coordinates, the baseline should sit on the rect's top edge.
+struct TextOnRectState;
+
+impl State for TextOnRectState {
+    fn new() -> Self {
+        TextOnRectState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        renderer.draw_rect(
+            &Rect::new(20.0, 58.0, 120.0, 2.0),
+            &Color::from_rgba(255, 0, 0, 255),
+        );
+        renderer.draw_text(
+            renderer.default_font(),
+            &Point::new(20.0, 60.0),
+            "Baseline",
+            24.0,
+            &Color::from_rgba(255, 255, 255, 255),
+        );
+    }
+}
+
+/// A font loaded from disk through the resource manager, next to the built in one.
+struct LoadedFontState {
+    font: Option<FontId>,
+}
+
+impl State for LoadedFontState {
+    fn new() -> Self {
+        LoadedFontState { font: None }
+    }
+
+    fn init(
+        &mut self,
+        _window: &mut Window,
+        graphics: &mut Graphics,
+        resources: &mut ResourceManager,
+    ) {
+        // A sans serif monospace face, which cannot be mistaken for the built in serif
+        let handle = resources
+            .load_font(
+                "mono",
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/tests/fixtures/DejaVuSansMono.ttf"
+                ),
+            )
+            .unwrap();
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        self.font = Some(renderer.add_font_resource(resources, handle).unwrap());
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        renderer.draw_text(
+            renderer.default_font(),
+            &Point::new(10.0, 80.0),
+            "Built in",
+            20.0,
+            &Color::from_rgba(255, 255, 255, 255),
+        );
+        renderer.draw_text(
+            self.font.unwrap(),
+            &Point::new(10.0, 30.0),
+            "Loaded",
+            28.0,
+            &Color::from_rgba(0, 255, 128, 255),
+        );
+    }
+}
+
+// Draws from a font file that is replaced by another face after the first frame
+struct ReloadedFontState {
+    path: PathBuf,
+    font: Option<FontId>,
+    frame: usize,
+}
+
+impl State for ReloadedFontState {
+    fn new() -> Self {
+        ReloadedFontState {
+            path: std::env::temp_dir().join(format!("slash-reload-{}.ttf", std::process::id())),
+            font: None,
+            frame: 0,
+        }
+    }
+
+    fn init(
+        &mut self,
+        _window: &mut Window,
+        graphics: &mut Graphics,
+        resources: &mut ResourceManager,
+    ) {
+        std::fs::copy(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/src/GoudyStMTT.ttf"),
+            &self.path,
+        )
+        .unwrap();
+
+        resources.set_hot_reload(true);
+        resources.set_poll_interval(Duration::from_millis(0));
+        let handle = resources.load_font("reloaded", &self.path).unwrap();
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        self.font = Some(renderer.add_font_resource(resources, handle).unwrap());
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        // Both atlases cache the old glyphs on the first frame
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        renderer.draw_text(
+            self.font.unwrap(),
+            &Point::new(10.0, 70.0),
+            "Reload",
+            28.0,
+            &Color::from_rgba(255, 255, 255, 255),
+        );
+        renderer.set_text_mode(TextMode::Sdf);
+        renderer.draw_text(
+            self.font.unwrap(),
+            &Point::new(10.0, 20.0),
+            "Reload",
+            28.0,
+            &Color::from_rgba(0, 255, 128, 255),
+        );
+        renderer.set_text_mode(TextMode::Bitmap);
+
+        if self.frame == 0 {
+            std::fs::copy(
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/tests/fixtures/DejaVuSansMono.ttf"
+                ),
+                &self.path,
+            )
+            .unwrap();
+            // Some file systems only keep whole seconds, which the first load may share
+            std::fs::File::options()
+                .write(true)
+                .open(&self.path)
+                .unwrap()
+                .set_modified(SystemTime::now() + Duration::from_secs(60))
+                .unwrap();
+        }
+        self.frame += 1;
+    }
+}
+
+impl Drop for ReloadedFontState {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Wrapped, centered text inside the box `measure_text` reports for it.
+struct TextLayoutState;
+
+impl State for TextLayoutState {
+    fn new() -> Self {
+        TextLayoutState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        let font = renderer.default_font();
+
+        let mut options = LayoutOptions::new();
+        options.max_width = Some(100.0);
+        options.align = TextAlign::Center;
+        options.line_spacing = 1.2;
+        let layout = renderer.layout_text(font, "Wrapped and centered text", 16.0, &options);
+        let bounds = layout.get_bounds();
+        let origin = Point::new(30.0, 100.0);
+
+        renderer.draw_rect(
+            &Rect::new(
+                origin.x + bounds.x,
+                origin.y + bounds.y,
+                bounds.width,
+                bounds.height,
+            ),
+            &Color::from_rgba(0, 0, 160, 255),
+        );
+        renderer.draw_layout(&layout, &origin, &Color::from_rgba(255, 255, 255, 255));
+
+        let label = renderer.measure_text(font, "Label", 20.0);
+        let label_origin = Point::new(80.0 - label.width / 2.0, 10.0 - label.y);
+        renderer.draw_text(
+            font,
+            &label_origin,
+            "Label",
+            20.0,
+            &Color::from_rgba(255, 255, 0, 255),
+        );
+    }
+}
+
+/// Spans of different sizes and colors wrapped as one paragraph, with underline and strikethrough.
+struct RichTextState;
+
+impl State for RichTextState {
+    fn new() -> Self {
+        RichTextState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        let font = renderer.default_font();
+
+        let text = RichText::new()
+            .with(TextSpan::new(
+                "<player> ",
+                font,
+                14.0,
+                Color::from_rgba(120, 200, 255, 255),
+            ))
+            .with(TextSpan::new(
+                "big ",
+                font,
+                26.0,
+                Color::from_rgba(255, 255, 255, 255),
+            ))
+            .with(TextSpan::new("news", font, 14.0, Color::from_rgba(255, 200, 0, 255)).underline())
+            .with(
+                TextSpan::new(" old price", font, 14.0, Color::from_rgba(255, 80, 80, 255))
+                    .strikethrough(),
+            );
+
+        let mut options = LayoutOptions::new();
+        options.max_width = Some(140.0);
+        renderer.draw_rich_text(&text, &Point::new(10.0, 90.0), &options);
+    }
+}
+
+/// Distance field text at a small and a large size, outlined and with a soft shadow.
+struct SdfTextState;
+
+impl State for SdfTextState {
+    fn new() -> Self {
+        SdfTextState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        let font = renderer.default_font();
+        let white = Color::from_rgba(255, 255, 255, 255);
+
+        renderer.set_text_mode(TextMode::Sdf);
+        renderer.draw_text(font, &Point::new(10.0, 95.0), "Tiny", 12.0, &white);
+
+        renderer.set_text_effects(
+            TextEffects::new()
+                .outline(2.0, Color::from_rgba(200, 40, 40, 255))
+                .shadow(Point::new(3.0, -3.0), 2.0, Color::from_rgba(0, 0, 0, 160)),
+        );
+        renderer.draw_text(font, &Point::new(10.0, 30.0), "Big", 56.0, &white);
+    }
+}
+
+/// A filled concave star, a closed stroke with round joins and an open curve with square caps.
+struct PathState;
+
+impl State for PathState {
+    fn new() -> Self {
+        PathState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+
+        let mut star = Path::new();
+        for i in 0..10 {
+            let angle = std::f32::consts::PI * (0.5 + i as f32 / 5.0);
+            let radius = if i % 2 == 0 { 35.0 } else { 14.0 };
+            let (x, y) = (45.0 + radius * angle.cos(), 60.0 + radius * angle.sin());
+            star = if i == 0 {
+                star.move_to(x, y)
+            } else {
+                star.line_to(x, y)
+            };
+        }
+        let star = star.close();
+        renderer.fill_path(&star, &Color::from_rgba(255, 200, 0, 255));
+        renderer.stroke_path(
+            &star,
+            &StrokeStyle::new(3.0).join(LineJoin::Round),
+            &Color::from_rgba(200, 60, 0, 255),
+        );
+
+        let wave = Path::new()
+            .move_to(95.0, 30.0)
+            .quad_to(110.0, 70.0, 125.0, 50.0)
+            .cubic_to(135.0, 35.0, 150.0, 100.0, 150.0, 100.0)
+            .arc(130.0, 100.0, 15.0, 0.0, std::f32::consts::PI / 2.0);
+        renderer.stroke_path(
+            &wave,
+            &StrokeStyle::new(4.0).cap(LineCap::Square),
+            &Color::from_rgba(0, 160, 255, 255),
+        );
+    }
+}
+
+struct RoundedRectState;
+
+impl State for RoundedRectState {
+    fn new() -> Self {
+        RoundedRectState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+
+        renderer.draw_styled_rect(
+            &Rect::new(10.0, 70.0, 60.0, 40.0),
+            &ShapeStyle::new().stroke(Color::from_rgba(255, 255, 255, 255), 2.0),
+        );
+        renderer.draw_rounded_rect(
+            &Rect::new(90.0, 70.0, 60.0, 40.0),
+            &CornerRadii::new(0.0, 20.0, 5.0, 12.0),
+            &ShapeStyle::new()
+                .fill(Color::from_rgba(40, 120, 220, 255))
+                .stroke(Color::from_rgba(255, 200, 0, 255), 3.0),
+        );
+        renderer.draw_rounded_rect(
+            &Rect::new(10.0, 10.0, 60.0, 50.0),
+            &CornerRadii::all(10.0),
+            &ShapeStyle::new().fill(Paint::LinearGradient {
+                start: Point::new(10.0, 10.0),
+                end: Point::new(70.0, 60.0),
+                start_color: Color::from_rgba(255, 0, 80, 255),
+                end_color: Color::from_rgba(0, 200, 255, 255),
+            }),
+        );
+        renderer.draw_rounded_rect(
+            &Rect::new(90.0, 10.0, 60.0, 50.0),
+            &CornerRadii::all(100.0),
+            &ShapeStyle::new()
+                .fill(Paint::RadialGradient {
+                    center: Point::new(120.0, 35.0),
+                    radius: 25.0,
+                    inner_color: Color::from_rgba(255, 255, 255, 255),
+                    outer_color: Color::from_rgba(0, 120, 0, 255),
+                })
+                .stroke(Color::from_rgba(0, 0, 0, 255), 1.0),
+        );
+    }
+}
+
+struct TransformState;
+
+impl State for TransformState {
+    fn new() -> Self {
+        TransformState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+
+        // Centered on (0, 0), twice as large and slightly turned
+        renderer.set_camera(Some(
+            Camera2D::new(Point::new(0.0, 0.0)).zoom(2.0).rotation(0.2),
+        ));
+        renderer.draw_rect(
+            &Rect::new(-35.0, -25.0, 70.0, 50.0),
+            &Color::from_rgba(40, 40, 80, 255),
+        );
+
+        for i in 0..4 {
+            renderer.push_transform(
+                &Transform::identity()
+                    .rotate(std::f32::consts::PI / 2.0 * i as f32)
+                    .translate(20.0, 0.0),
+            );
+            renderer.draw_circle(
+                &Point::new(0.0, 0.0),
+                6.0,
+                &ShapeStyle::new()
+                    .fill(Color::from_rgba(255, 120, 0, 255))
+                    .stroke(Color::from_rgba(255, 255, 255, 255), 1.0),
+            );
+
+            renderer.push_transform(&Transform::identity().scale(0.5, 0.5).rotate(0.8));
+            renderer.draw_rect(
+                &Rect::new(-5.0, -5.0, 10.0, 10.0),
+                &Color::from_rgba(0, 200, 120, 255),
+            );
+            renderer.pop_transform();
+            renderer.pop_transform();
+        }
+
+        let font = renderer.default_font();
+        renderer.push_transform(&Transform::new(1.0, 0.0, 0.5, 1.0, -12.0, -4.0));
+        renderer.draw_text(
+            font,
+            &Point::new(0.0, 0.0),
+            "Slash",
+            8.0,
+            &Color::from_rgba(255, 255, 255, 255),
+        );
+        renderer.pop_transform();
+        renderer.set_camera(None);
+    }
+}
+
+struct ClipState;
+
+impl State for ClipState {
+    fn new() -> Self {
+        ClipState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        let stripes = |renderer: &mut SpriteRenderer, x: f32, color: &Color| {
+            for i in 0..12 {
+                renderer.draw_line(
+                    x,
+                    i as f32 * 10.0,
+                    x + 80.0,
+                    i as f32 * 10.0 + 40.0,
+                    3.0,
+                    color,
+                );
+            }
+        };
+
+        // A scrolling panel with a nested one, clipped to where both overlap
+        renderer.push_clip_rect(&Rect::new(5.0, 60.0, 70.0, 50.0));
+        stripes(renderer, 0.0, &Color::from_rgba(255, 80, 80, 255));
+        renderer.push_transform(&Transform::translation(40.0, 40.0));
+        renderer.push_clip_rect(&Rect::new(0.0, 0.0, 60.0, 30.0));
+        renderer.draw_rect(
+            &Rect::new(-40.0, -40.0, 160.0, 120.0),
+            &Color::from_rgba(80, 80, 255, 160),
+        );
+        renderer.pop_clip();
+        renderer.pop_transform();
+        renderer.pop_clip();
+
+        // A turned clip rect falls back to a mask
+        renderer.push_transform(
+            &Transform::translation(120.0, 85.0).rotate(std::f32::consts::PI / 6.0),
+        );
+        renderer.push_clip_rect(&Rect::new(-20.0, -15.0, 40.0, 30.0));
+        renderer.pop_transform();
+        stripes(renderer, 80.0, &Color::from_rgba(80, 255, 80, 255));
+        renderer.pop_clip();
+
+        // A star shaped mask, with text clipped to it and a circle within
+        let mut star = Path::new();
+        for i in 0..10 {
+            let angle = std::f32::consts::PI * (0.5 + i as f32 / 5.0);
+            let radius = if i % 2 == 0 { 28.0 } else { 12.0 };
+            let (x, y) = (80.0 + radius * angle.cos(), 30.0 + radius * angle.sin());
+            star = if i == 0 {
+                star.move_to(x, y)
+            } else {
+                star.line_to(x, y)
+            };
+        }
+        let star = star.close();
+        renderer.push_clip_mask(|renderer| {
+            renderer.fill_path(&star, &Color::from_rgba(255, 255, 255, 255))
+        });
+        stripes(renderer, 40.0, &Color::from_rgba(255, 200, 0, 255));
+        renderer.push_clip_mask(|renderer| {
+            renderer.draw_circle(
+                &Point::new(80.0, 30.0),
+                10.0,
+                &ShapeStyle::new().fill(Color::from_rgba(255, 255, 255, 255)),
+            )
+        });
+        renderer.draw_rect(
+            &Rect::new(0.0, 0.0, 160.0, 60.0),
+            &Color::from_rgba(0, 0, 0, 255),
+        );
+        renderer.pop_clip();
+        let font = renderer.default_font();
+        renderer.draw_text(
+            font,
+            &Point::new(58.0, 24.0),
+            "Mask",
+            16.0,
+            &Color::from_rgba(255, 255, 255, 255),
+        );
+        renderer.pop_clip();
+    }
+}
+
+// Pushes a clip every frame and forgets to pop it
+struct UnbalancedClipState;
+
+impl State for UnbalancedClipState {
+    fn new() -> Self {
+        UnbalancedClipState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        renderer.push_clip_rect(&Rect::new(0.0, 0.0, 10.0, 10.0));
+    }
+}
+
+struct SpriteState {
+    texture: Option<Texture>,
+}
+
+impl State for SpriteState {
+    fn new() -> Self {
+        SpriteState { texture: None }
+    }
+
+    fn init(
+        &mut self,
+        _window: &mut Window,
+        _graphics: &mut Graphics,
+        _resources: &mut ResourceManager,
+    ) {
+        let checkerboard = image::RgbaImage::from_fn(8, 8, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgba([255, 255, 255, 255])
+            } else {
+                image::Rgba([255, 0, 0, 128])
+            }
+        });
+        self.texture = Some(Texture::from_image(&checkerboard));
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        let texture = self.texture.as_ref().unwrap();
+        renderer.draw_sprite(
+            texture,
+            &Rect::new(0.0, 0.0, 8.0, 8.0),
+            &Rect::new(10.0, 10.0, 64.0, 64.0),
+            &Color::from_rgba(255, 255, 255, 255),
+        );
+        renderer.draw_sprite(
+            texture,
+            &Rect::new(2.0, 2.0, 4.0, 4.0),
+            &Rect::new(90.0, 20.0, 60.0, 90.0),
+            &Color::from_rgba(0, 255, 0, 255),
+        );
+    }
+}
+
+struct ManyShapesState;
+
+impl State for ManyShapesState {
+    fn new() -> Self {
+        ManyShapesState
+    }
+
+    fn render(&mut self, graphics: &mut Graphics, _state: &AppState) {
+        let renderer = graphics.sprite_renderer.as_mut().unwrap();
+        for i in 0..2000 {
+            let x = (i % 40) as f32 * 4.0;
+            let y = (i / 40) as f32 * 2.4;
+            let color = Color::from_rgba((i % 256) as u8, (i / 8 % 256) as u8, 128, 255);
+            if i % 3 == 0 {
+                renderer.draw_ellipse(
+                    &Point::new(x + 2.0, y + 1.2),
+                    2.0,
+                    1.2,
+                    &ShapeStyle::new().fill(color),
+                );
+            } else if i % 3 == 1 {
+                renderer.draw_rect(&Rect::new(x, y, 3.0, 2.0), &color);
+            } else {
+                renderer.draw_line(x, y, x + 4.0, y + 2.4, 1.0, &color);
+            }
+        }
+    }
+}
+
+#[test]
+fn draw_rect() {
+    check::<RectState>("draw_rect");
+}
+
+#[test]
+fn draw_circle() {
+    check::<CircleState>("draw_circle");
+}
+
+#[test]
+fn draw_line() {
+    check::<LineState>("draw_line");
+}
+
+#[test]
+fn draw_text() {
+    check::<TextState>("draw_text");
+}
+
+#[test]
+fn draw_text_on_rect() {
+    check::<TextOnRectState>("draw_text_on_rect");
+}
+
+#[test]
+fn draw_loaded_font() {
+    check::<LoadedFontState>("draw_loaded_font");
+}
+
+#[test]
+fn draw_reloaded_font() {
+    check::<ReloadedFontState>("draw_reloaded_font");
+}
+
+#[test]
+fn draw_text_layout() {
+    check::<TextLayoutState>("draw_text_layout");
+}
+
+#[test]
+fn draw_rich_text() {
+    check::<RichTextState>("draw_rich_text");
+}
+
+#[test]
+fn draw_sdf_text() {
+    check::<SdfTextState>("draw_sdf_text");
+}
+
+#[test]
+fn draw_paths() {
+    check::<PathState>("draw_paths");
+}
+
+#[test]
+fn draw_rounded_rects() {
+    check::<RoundedRectState>("draw_rounded_rects");
+}
+
+#[test]
+fn draw_transformed() {
+    check::<TransformState>("draw_transformed");
+}
+
+#[test]
+fn draw_clipped() {
+    check::<ClipState>("draw_clipped");
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "never popped")]
+fn unpopped_clips_are_caught() {
+    let _ = testing::render_frames(UnbalancedClipState, WIDTH, HEIGHT, 2);
+}
+
+#[test]
+fn draw_many_shapes() {
+    check::<ManyShapesState>("draw_many_shapes");
+}
+
+#[test]
+fn draw_sprite() {
+    check::<SpriteState>("draw_sprite");
+}